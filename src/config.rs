@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// 配置文件，字段与 Monitor 命令的参数一一对应
+// 所有字段都是可选的，命令行显式传入的参数优先于配置文件
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub interval: Option<u64>,
+    pub interval_ms: Option<u64>,
+    pub top_n: Option<usize>,
+    pub pool_ids: Option<Vec<String>>,
+    pub watchlist: Option<PathBuf>,
+    pub pool_type: Option<String>,
+    pub sort_by: Option<String>,
+    pub momentum_weights: Option<String>,
+    pub min_burn_percent: Option<f64>,
+    pub impact_size: Option<f64>,
+    pub max_slippage: Option<f64>,
+    pub max_age: Option<String>,
+    pub min_age: Option<String>,
+    pub pages: Option<u32>,
+    pub count: Option<u64>,
+    pub fail_fast: Option<bool>,
+    pub failure_alert_after: Option<u64>,
+    pub no_preflight: Option<bool>,
+    pub memory_report_every: Option<u64>,
+    pub top_gainers: Option<usize>,
+    pub top_losers: Option<usize>,
+    pub movers_min_volume: Option<f64>,
+    pub compare: Option<Vec<String>>,
+    pub correlation_window: Option<String>,
+    pub decouple_alert: Option<f64>,
+    pub quiet: Option<bool>,
+    pub output: Option<String>,
+    pub output_truncate: Option<bool>,
+    pub color: Option<String>,
+    pub layout: Option<String>,
+    pub price_alert: Option<f64>,
+    pub volume_alert: Option<f64>,
+    pub tvl_drain_alert: Option<f64>,
+    pub volume_zscore_alert: Option<f64>,
+    pub reserve_alert: Option<f64>,
+    pub alert_aggregate: Option<usize>,
+    pub alert_cooldown: Option<u64>,
+    pub price_targets: Option<Vec<String>>,
+    // 按池子ID覆盖警报阈值
+    pub pool_thresholds: Option<HashMap<String, PoolThresholdConfig>>,
+    pub notify_stdout: Option<bool>,
+    pub telegram_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub discord_webhook: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub email_from: Option<String>,
+    pub email_to: Option<Vec<String>>,
+    pub history_file: Option<PathBuf>,
+    pub jsonl_out: Option<PathBuf>,
+    pub history_capacity: Option<usize>,
+    pub backfill_csv: Option<PathBuf>,
+    pub market_cap: Option<bool>,
+    pub market_cap_concurrency: Option<usize>,
+    pub market_cap_interval: Option<u64>,
+    pub supply_cache_ttl: Option<u64>,
+    pub sol_price_max_deviation: Option<f64>,
+    pub anomaly_ratio: Option<f64>,
+    pub anomaly_min_pools: Option<usize>,
+    pub market_cap_mode: Option<String>,
+    pub rpc_url: Option<String>,
+    pub fixture: Option<PathBuf>,
+    pub dump_raw: Option<PathBuf>,
+    pub dump_raw_gzip: Option<bool>,
+}
+
+// [pool_thresholds.<pool_id>] 表中的字段，未设置的沿用全局阈值
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolThresholdConfig {
+    pub price_alert: Option<f64>,
+    pub volume_alert: Option<f64>,
+}
+
+impl Config {
+    // 从 TOML 文件加载配置
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.interval == Some(0) || self.interval_ms == Some(0) {
+            return Err(anyhow::anyhow!("interval must be greater than 0"));
+        }
+        if self.market_cap_interval == Some(0) {
+            return Err(anyhow::anyhow!(
+                "market_cap_interval must be greater than 0"
+            ));
+        }
+        if self.market_cap_concurrency == Some(0) {
+            return Err(anyhow::anyhow!(
+                "market_cap_concurrency must be greater than 0"
+            ));
+        }
+        if let Some(rpc_url) = &self.rpc_url {
+            if !rpc_url.starts_with("http://") && !rpc_url.starts_with("https://") {
+                return Err(anyhow::anyhow!(
+                    "rpc_url must be an http(s) URL, got '{}'",
+                    rpc_url
+                ));
+            }
+        }
+        for threshold in [
+            self.price_alert,
+            self.volume_alert,
+            self.tvl_drain_alert,
+            self.volume_zscore_alert,
+            self.reserve_alert,
+            self.min_burn_percent,
+            self.impact_size,
+            self.max_slippage,
+            self.sol_price_max_deviation,
+            self.anomaly_ratio,
+        ]
+        .into_iter()
+        .chain(
+            self.pool_thresholds
+                .iter()
+                .flat_map(|thresholds| thresholds.values())
+                .flat_map(|threshold| [threshold.price_alert, threshold.volume_alert]),
+        )
+        .flatten()
+        {
+            if threshold < 0.0 {
+                return Err(anyhow::anyhow!(
+                    "alert thresholds must not be negative, got {}",
+                    threshold
+                ));
+            }
+        }
+        Ok(())
+    }
+}
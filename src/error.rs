@@ -0,0 +1,88 @@
+use reqwest::StatusCode;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::RpcError;
+use solana_sdk::pubkey::ParsePubkeyError;
+
+pub type Result<T> = std::result::Result<T, MonitorError>;
+
+// 获取、解析池子数据和计算市值时可能出现的错误，库的调用方可以按变体处理
+#[derive(Debug, thiserror::Error)]
+pub enum MonitorError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    // Raydium API 返回 success: false
+    #[error("Raydium API error: {msg}{}", request_id_suffix(.request_id))]
+    ApiError {
+        msg: String,
+        request_id: Option<String>,
+    },
+
+    #[error("Raydium API request failed with status {0}")]
+    Status(StatusCode),
+
+    #[error("Invalid JSON from Raydium API")]
+    InvalidJson(#[source] serde_json::Error),
+
+    // 响应中缺少字段或字段格式不对
+    #[error("Failed to parse {field}")]
+    Parse { field: String },
+
+    // ClientError 较大，装箱以免所有 Result 都变大
+    #[error(transparent)]
+    Rpc(Box<ClientError>),
+
+    #[error("Invalid token address: {0}")]
+    InvalidAddress(#[from] ParsePubkeyError),
+
+    #[error("All SOL price sources failed ({0})")]
+    SolPrice(String),
+
+    #[error("No stored history for pool {pool_id}")]
+    NoHistory { pool_id: String },
+
+    #[error("Raydium client already initialized")]
+    ClientInitialized,
+}
+
+impl MonitorError {
+    pub fn parse(field: impl Into<String>) -> Self {
+        MonitorError::Parse {
+            field: field.into(),
+        }
+    }
+
+    // 服务端返回 429（请求过多）。公共 RPC 节点在负载高时经常限流，
+    // RPC 客户端自身重试后仍失败时以 HTTP 状态或 JSON-RPC 错误码的形式返回
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            MonitorError::Status(status) => *status == StatusCode::TOO_MANY_REQUESTS,
+            MonitorError::Rpc(e) => match e.kind() {
+                ClientErrorKind::Reqwest(e) => {
+                    e.status().is_some_and(|status| status.as_u16() == 429)
+                }
+                ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => *code == 429,
+                // 例如查询集群版本失败时被包装成字符串的 "RPC response error 429"
+                _ => {
+                    let message = e.to_string();
+                    message.contains("429 Too Many Requests")
+                        || message.contains("RPC response error 429")
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+impl From<ClientError> for MonitorError {
+    fn from(e: ClientError) -> Self {
+        MonitorError::Rpc(Box::new(e))
+    }
+}
+
+fn request_id_suffix(request_id: &Option<String>) -> String {
+    request_id
+        .as_ref()
+        .map(|id| format!(" (request id {})", id))
+        .unwrap_or_default()
+}
@@ -0,0 +1,8 @@
+pub mod client;
+pub mod error;
+pub mod mointor;
+pub mod notifier;
+pub mod raydium_pool;
+pub mod timestamp;
+pub mod utils;
+pub mod watchlist;
@@ -1,48 +1,1371 @@
-pub mod mointor;
-pub mod raydium_pool;
-pub mod utils;
+mod config;
 
-use log::LevelFilter;
+use config::Config;
+use raydium_mointor::client;
+use raydium_mointor::mointor::{CheckOutcome, MonitorService};
+use raydium_mointor::notifier::{
+    Alert, DiscordNotifier, EmailNotifier, Notifiers, SmtpConfig, StdoutNotifier, TelegramNotifier,
+};
+use raydium_mointor::raydium_pool::{
+    check_raydium_pools_pages, disappeared_pool_alerts, display_limit, fetch_raydium_data,
+    format_age, format_disappeared_pools, format_history_table, format_new_pools, format_pool_data,
+    format_price_crossings, format_snapshot_json, format_snapshot_text, format_top_movers,
+    new_pool_alerts, price_crossing_alerts, AlertKind, AlertThresholds, AnomalyThresholds,
+    ColorMode, DisplayOptions, FileSource, HttpSource, IdsSource, Layout, MarketCapMode,
+    MomentumWeights, OutputFormat, OutputSink, OutputTarget, PoolDataResult, PoolDataSource,
+    PoolMonitor, PoolThreshold, PriceTarget, RawDump, SnapshotArchive, SortBy,
+};
+use raydium_mointor::utils::{get_sol_price, DEFAULT_RPC_URL};
+use raydium_mointor::watchlist::{load_watchlist, update_watchlist};
+use solana_client::nonblocking::rpc_client::RpcClient;
 use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use structopt::clap::ArgMatches;
 use structopt::StructOpt;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing_subscriber::{filter::LevelFilter, EnvFilter};
+
+#[derive(StructOpt, Debug)]
+pub struct MonitorOpts {
+    /// TOML 配置文件，命令行参数优先
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// 检查间隔（秒）
+    #[structopt(short, long, default_value = "30")]
+    interval: u64,
+
+    /// 毫秒级检查间隔，设置后覆盖 --interval
+    #[structopt(long)]
+    interval_ms: Option<u64>,
+
+    /// 显示前N个池子（0 表示全部）
+    #[structopt(short, long, default_value = "20")]
+    top_n: usize,
+
+    /// 只监控指定的池子ID，可重复（不再扫描交易量排行）
+    #[structopt(long = "pool-id", number_of_values = 1)]
+    pool_ids: Vec<String>,
+
+    /// 从关注列表文件读取要监控的池子ID（.json 文件为 JSON 数组，否则每行一个ID），与 --pool-id 合并
+    #[structopt(long, parse(from_os_str))]
+    watchlist: Option<PathBuf>,
+
+    /// 只监控指定类型的池子（amm|clmm|Standard|Concentrated|OpenBookMarket 等）
+    #[structopt(long)]
+    pool_type: Option<String>,
+
+    /// 排序方式 (volume|momentum)
+    #[structopt(long, default_value = "volume")]
+    sort_by: SortBy,
+
+    /// 显示5分钟涨幅最大的N个池子，代替按交易量排序的列表（0 表示不显示）
+    #[structopt(long, default_value = "0")]
+    top_gainers: usize,
+
+    /// 显示5分钟跌幅最大的N个池子，代替按交易量排序的列表（0 表示不显示）
+    #[structopt(long, default_value = "0")]
+    top_losers: usize,
+
+    /// 涨跌幅榜只包含24小时交易量不低于该值(USD)的池子
+    #[structopt(long, default_value = "10000")]
+    movers_min_volume: f64,
+
+    /// 显示两个池子价格的滚动相关系数 <pool_a> <pool_b>（两个池子都需要在监控范围内）
+    #[structopt(long, number_of_values = 2, value_names = &["pool_a", "pool_b"])]
+    compare: Vec<String>,
+
+    /// 计算相关系数的时间窗口，例如 30m、1h、24h
+    #[structopt(long, default_value = "1h", parse(try_from_str = parse_age))]
+    correlation_window: chrono::Duration,
+
+    /// --compare 的两个池子相关系数低于该值时报警（0 表示不报警）
+    #[structopt(long, default_value = "0")]
+    decouple_alert: f64,
+
+    /// 动量分数权重 <price_5m>,<price_1h>,<volume_1h>
+    #[structopt(long, default_value = "0.4,0.3,0.3")]
+    momentum_weights: MomentumWeights,
+
+    /// 只显示 LP 销毁比例不低于该值(%)的池子；--pool-id 指定的池子不过滤，只显示风险提示
+    #[structopt(long, default_value = "0")]
+    min_burn_percent: f64,
+
+    /// 估算价格冲击使用的交易规模(USD)，0 表示不显示
+    #[structopt(long, default_value = "1000")]
+    impact_size: f64,
+
+    /// 只显示按 --impact-size 估算的价格冲击不超过该值(%)的池子；--pool-id 指定的池子不过滤
+    #[structopt(long)]
+    max_slippage: Option<f64>,
+
+    /// 只显示开池时间不超过该时长的池子，例如 30m、24h、7d（开池时间未知的池子不显示）
+    #[structopt(long, parse(try_from_str = parse_age))]
+    max_age: Option<chrono::Duration>,
+
+    /// 只显示开池时间不少于该时长的池子，例如 1h（开池时间未知的池子不显示）
+    #[structopt(long, parse(try_from_str = parse_age))]
+    min_age: Option<chrono::Duration>,
+
+    /// 获取的页数（每页100个池子，按交易量排序）；只有取到最后一页时才能检测池子下架
+    #[structopt(long, default_value = "1")]
+    pages: u32,
+
+    /// 轮询N次后退出（0 表示一直运行）
+    #[structopt(long, default_value = "0")]
+    count: u64,
+
+    /// 获取数据失败时立即以非零状态退出（默认记录错误并继续下一轮）
+    #[structopt(long, conflicts_with = "resilient")]
+    fail_fast: bool,
+
+    /// 获取数据失败时记录错误并继续下一轮（默认行为）
+    #[structopt(long)]
+    resilient: bool,
+
+    /// 跳过启动自检（检查 Raydium API、SOL 价格、RPC 节点和通知渠道）
+    #[structopt(long)]
+    no_preflight: bool,
+
+    /// 连续失败N次时输出 API 可能宕机的警告，之后每N次重复（0 表示不警告）
+    #[structopt(long, default_value = "5")]
+    failure_alert_after: u64,
+
+    /// 每N次轮询在日志中记录一次历史数据的内存占用（0 表示不记录）
+    #[structopt(long, default_value = "0")]
+    memory_report_every: u64,
+
+    /// 安静模式：只输出警报，没有警报时只输出一行提示
+    #[structopt(short, long)]
+    quiet: bool,
+
+    /// 输出目标 (stdout|file:PATH|both:PATH)，JSON lines 快照不受影响
+    #[structopt(long, default_value = "stdout")]
+    output: OutputTarget,
+
+    /// 启动时清空输出文件（默认追加）
+    #[structopt(long)]
+    output_truncate: bool,
+
+    /// 颜色输出 (auto|always|never)
+    #[structopt(long, default_value = "auto")]
+    color: ColorMode,
+
+    /// 关闭颜色输出，等同于 --color never（设置 NO_COLOR 环境变量同样生效）
+    #[structopt(long)]
+    no_color: bool,
+
+    /// 显示布局 (detailed|table)
+    #[structopt(long, default_value = "detailed")]
+    layout: Layout,
+
+    /// 价格变化警报阈值(%)
+    #[structopt(long, default_value = "1.0")]
+    price_alert: f64,
+
+    /// 熔断：异常池子（价格为0、TVL为负或价格完全相同）占比超过该值(%)时跳过本轮警报（0 表示不检查）
+    #[structopt(long, default_value = "50")]
+    anomaly_ratio: f64,
+
+    /// 池子数少于该值时不做异常检测
+    #[structopt(long, default_value = "3")]
+    anomaly_min_pools: usize,
+
+    /// 交易量变化警报阈值(%)
+    #[structopt(long, default_value = "5.0")]
+    volume_alert: f64,
+
+    /// 短时间(5m/15m/1h) TVL 下降警报阈值(%)
+    #[structopt(long, default_value = "20.0")]
+    tvl_drain_alert: f64,
+
+    /// 交易量 z-score 警报阈值（相对历史均值的标准差倍数）
+    #[structopt(long, default_value = "3.0")]
+    volume_zscore_alert: f64,
+
+    /// 两次轮询之间储备单边变化警报阈值(%)，0 表示关闭
+    #[structopt(long, default_value = "0")]
+    reserve_alert: f64,
+
+    /// 同一轮中同类警报超过K个池子时合并为一条汇总（0 表示不合并）
+    #[structopt(long, default_value = "0")]
+    alert_aggregate: usize,
+
+    /// 同一警报的冷却时间（秒），冷却期内不重复报警
+    #[structopt(long, default_value = "300")]
+    alert_cooldown: u64,
+
+    /// 目标价警报 <pool_id>:<above|below>:<value>，可重复
+    #[structopt(long = "price-target", number_of_values = 1)]
+    price_targets: Vec<PriceTarget>,
+
+    /// 每条警报额外输出一行 ALERT 到标准输出
+    #[structopt(long)]
+    notify_stdout: bool,
+
+    /// Telegram Bot token，与 --telegram-chat-id 一起设置后发送警报
+    #[structopt(long, env = "TELEGRAM_BOT_TOKEN", hide_env_values = true)]
+    telegram_token: Option<String>,
+
+    /// 接收警报的 Telegram chat ID
+    #[structopt(long)]
+    telegram_chat_id: Option<String>,
+
+    /// 接收警报的 Discord webhook 地址
+    #[structopt(long, env = "DISCORD_WEBHOOK_URL", hide_env_values = true)]
+    discord_webhook: Option<String>,
+
+    /// SMTP 服务器地址，与 --email-from/--email-to 一起设置后发送邮件警报
+    #[structopt(long, env = "SMTP_HOST")]
+    smtp_host: Option<String>,
+
+    /// SMTP 端口（STARTTLS）
+    #[structopt(long, env = "SMTP_PORT", default_value = "587")]
+    smtp_port: u16,
+
+    /// SMTP 用户名
+    #[structopt(long, env = "SMTP_USERNAME")]
+    smtp_username: Option<String>,
+
+    /// SMTP 密码
+    #[structopt(long, env = "SMTP_PASSWORD", hide_env_values = true)]
+    smtp_password: Option<String>,
+
+    /// 邮件发件人地址
+    #[structopt(long)]
+    email_from: Option<String>,
+
+    /// 邮件收件人地址，可重复
+    #[structopt(long = "email-to", number_of_values = 1)]
+    email_to: Vec<String>,
+
+    /// 单个池子的警报阈值 <pool_id>:<price>:<volume>，留空沿用全局值，可重复
+    #[structopt(long = "pool-threshold", number_of_values = 1)]
+    pool_thresholds: Vec<PoolThreshold>,
+
+    /// 历史数据持久化文件
+    #[structopt(long, parse(from_os_str))]
+    history_file: Option<PathBuf>,
+
+    /// 每次轮询的完整快照追加到 JSON lines 文件（按日期轮转）
+    #[structopt(long, parse(from_os_str))]
+    jsonl_out: Option<PathBuf>,
+
+    /// 每个池子最多保留的历史记录数（0 表示只按7天清理）
+    #[structopt(long, default_value = "0")]
+    history_capacity: usize,
+
+    /// 启动时从 CSV 导入历史快照（列：pool_id,timestamp,price,volume_24h,tvl）
+    #[structopt(long, parse(from_os_str))]
+    backfill_csv: Option<PathBuf>,
+
+    /// 计算显示池子的市值（需要 RPC 查询代币供应量）
+    #[structopt(long)]
+    market_cap: bool,
+
+    /// 市值计算方式 (rpc|fdv)，fdv 使用池子储备估算，不发送 RPC 请求
+    #[structopt(long, default_value = "rpc")]
+    market_cap_mode: MarketCapMode,
+
+    /// 市值查询间隔（秒），独立于价格轮询，避免触发 RPC 限流
+    #[structopt(long, default_value = "300")]
+    market_cap_interval: u64,
+
+    /// 代币供应量的缓存时间（秒），供应量很少变化，缓存期内不再请求 RPC（0 表示不缓存）
+    #[structopt(long, default_value = "600")]
+    supply_cache_ttl: u64,
+
+    /// SOL 价格相对上一次偏离超过该比例(%)时视为异常值并沿用缓存价格（0 表示不检查）
+    #[structopt(long, default_value = "20")]
+    sol_price_max_deviation: f64,
+
+    /// 市值查询的最大并发数
+    #[structopt(long, default_value = "5")]
+    market_cap_concurrency: usize,
+
+    /// Solana RPC 节点地址
+    #[structopt(long, env = "SOLANA_RPC_URL", default_value = DEFAULT_RPC_URL)]
+    rpc_url: String,
+
+    /// 使用保存的 API 响应文件代替实时请求（离线模式）
+    #[structopt(long, parse(from_os_str))]
+    fixture: Option<PathBuf>,
+
+    /// 把每次轮询的原始 API 响应保存到该目录（带时间戳的文件，默认关闭）
+    #[structopt(long, parse(from_os_str))]
+    dump_raw: Option<PathBuf>,
+
+    /// 用 gzip 压缩 --dump-raw 保存的文件
+    #[structopt(long)]
+    dump_raw_gzip: bool,
+}
+
+impl MonitorOpts {
+    // 根据参数注册警报通知渠道
+    fn notifiers(&self) -> anyhow::Result<Notifiers> {
+        let mut notifiers = Notifiers::new();
+        if self.notify_stdout {
+            notifiers.add(Box::new(StdoutNotifier));
+        }
+        match (&self.telegram_token, &self.telegram_chat_id) {
+            (Some(token), Some(chat_id)) => notifiers.add(Box::new(TelegramNotifier::new(
+                token.clone(),
+                chat_id.clone(),
+            ))),
+            (None, None) => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "--telegram-token and --telegram-chat-id must be set together"
+                ))
+            }
+        }
+        if let Some(webhook_url) = &self.discord_webhook {
+            notifiers.add(Box::new(DiscordNotifier::new(webhook_url.clone())));
+        }
+        if let Some(host) = &self.smtp_host {
+            let from = self
+                .email_from
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("--smtp-host requires --email-from"))?;
+            notifiers.add(Box::new(EmailNotifier::new(SmtpConfig {
+                host: host.clone(),
+                port: self.smtp_port,
+                username: self.smtp_username.clone(),
+                password: self.smtp_password.clone(),
+                from,
+                to: self.email_to.clone(),
+            })?));
+        }
+        Ok(notifiers)
+    }
+
+    // --interval-ms 优先于 --interval
+    fn poll_interval(&self) -> anyhow::Result<Duration> {
+        let interval = match self.interval_ms {
+            Some(interval_ms) => Duration::from_millis(interval_ms),
+            None => Duration::from_secs(self.interval),
+        };
+        if interval.is_zero() {
+            return Err(anyhow::anyhow!("interval must be greater than 0"));
+        }
+        Ok(interval)
+    }
+
+    // 指定了 --config 时读取并合并配置文件
+    fn load_config(&mut self, matches: &ArgMatches, subcommand: &str) -> anyhow::Result<()> {
+        let Some(path) = self.config.clone() else {
+            return Ok(());
+        };
+        let config = Config::load(&path)?;
+        if let Some(subcommand_matches) = matches.subcommand_matches(subcommand) {
+            self.merge_config(config, subcommand_matches)?;
+        }
+        tracing::info!("Loaded config from {}", path.display());
+        Ok(())
+    }
+
+    // 合并配置文件：只有命令行未显式传入的参数才使用配置文件的值
+    fn merge_config(&mut self, config: Config, matches: &ArgMatches) -> anyhow::Result<()> {
+        let from_cli = |name: &str| matches.occurrences_of(name) > 0;
+
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(
+                    if let Some(value) = config.$field {
+                        if !from_cli(stringify!($field)) {
+                            self.$field = value;
+                        }
+                    }
+                )*
+            };
+        }
+        merge!(
+            interval,
+            top_n,
+            pages,
+            count,
+            fail_fast,
+            failure_alert_after,
+            no_preflight,
+            memory_report_every,
+            top_gainers,
+            top_losers,
+            movers_min_volume,
+            decouple_alert,
+            history_capacity,
+            price_alert,
+            volume_alert,
+            tvl_drain_alert,
+            volume_zscore_alert,
+            reserve_alert,
+            min_burn_percent,
+            impact_size,
+            alert_aggregate,
+            alert_cooldown,
+            notify_stdout,
+            quiet,
+            output_truncate,
+            smtp_port,
+            market_cap,
+            market_cap_concurrency,
+            market_cap_interval,
+            supply_cache_ttl,
+            sol_price_max_deviation,
+            anomaly_ratio,
+            anomaly_min_pools,
+            dump_raw_gzip
+        );
+
+        // 命令行的 --resilient 覆盖配置文件中的 fail_fast
+        if self.resilient {
+            self.fail_fast = false;
+        }
+        if config.interval_ms.is_some() && !from_cli("interval_ms") && !from_cli("interval") {
+            self.interval_ms = config.interval_ms;
+        }
+        if let Some(color) = config.color.filter(|_| !from_cli("color")) {
+            self.color = color.parse()?;
+        }
+        if let Some(output) = config.output.filter(|_| !from_cli("output")) {
+            self.output = output.parse()?;
+        }
+        if let Some(layout) = config.layout.filter(|_| !from_cli("layout")) {
+            self.layout = layout.parse()?;
+        }
+        if let Some(sort_by) = config.sort_by.filter(|_| !from_cli("sort_by")) {
+            self.sort_by = sort_by.parse()?;
+        }
+        if let Some(weights) = config
+            .momentum_weights
+            .filter(|_| !from_cli("momentum_weights"))
+        {
+            self.momentum_weights = weights.parse()?;
+        }
+        if let Some(mode) = config
+            .market_cap_mode
+            .filter(|_| !from_cli("market_cap_mode"))
+        {
+            self.market_cap_mode = mode.parse()?;
+        }
+        if let Some(targets) = config.price_targets.filter(|_| !from_cli("price_targets")) {
+            self.price_targets = targets
+                .iter()
+                .map(|target| target.parse())
+                .collect::<anyhow::Result<_>>()?;
+        }
+        if let Some(pool_thresholds) = config
+            .pool_thresholds
+            .filter(|_| !from_cli("pool_thresholds"))
+        {
+            self.pool_thresholds = pool_thresholds
+                .into_iter()
+                .map(|(pool_id, threshold)| PoolThreshold {
+                    pool_id,
+                    price_alert: threshold.price_alert,
+                    volume_alert: threshold.volume_alert,
+                })
+                .collect();
+        }
+        if let Some(pool_ids) = config.pool_ids.filter(|_| !from_cli("pool_ids")) {
+            self.pool_ids = pool_ids;
+        }
+        if config.watchlist.is_some() && !from_cli("watchlist") {
+            self.watchlist = config.watchlist;
+        }
+        if config.max_slippage.is_some() && !from_cli("max_slippage") {
+            self.max_slippage = config.max_slippage;
+        }
+        if config.pool_type.is_some() && !from_cli("pool_type") {
+            self.pool_type = config.pool_type;
+        }
+        if config.telegram_token.is_some() && self.telegram_token.is_none() {
+            self.telegram_token = config.telegram_token;
+        }
+        if config.telegram_chat_id.is_some() && !from_cli("telegram_chat_id") {
+            self.telegram_chat_id = config.telegram_chat_id;
+        }
+        if config.discord_webhook.is_some() && self.discord_webhook.is_none() {
+            self.discord_webhook = config.discord_webhook;
+        }
+        if config.smtp_host.is_some() && self.smtp_host.is_none() {
+            self.smtp_host = config.smtp_host;
+        }
+        if config.smtp_username.is_some() && self.smtp_username.is_none() {
+            self.smtp_username = config.smtp_username;
+        }
+        if config.smtp_password.is_some() && self.smtp_password.is_none() {
+            self.smtp_password = config.smtp_password;
+        }
+        if config.email_from.is_some() && !from_cli("email_from") {
+            self.email_from = config.email_from;
+        }
+        if let Some(email_to) = config.email_to.filter(|_| !from_cli("email_to")) {
+            self.email_to = email_to;
+        }
+        if let Some(compare) = config.compare.filter(|_| !from_cli("compare")) {
+            self.compare = compare;
+        }
+        if let Some(window) = config
+            .correlation_window
+            .filter(|_| !from_cli("correlation_window"))
+        {
+            self.correlation_window = parse_age(&window)?;
+        }
+        if let Some(max_age) = config.max_age.filter(|_| !from_cli("max_age")) {
+            self.max_age = Some(parse_age(&max_age)?);
+        }
+        if let Some(min_age) = config.min_age.filter(|_| !from_cli("min_age")) {
+            self.min_age = Some(parse_age(&min_age)?);
+        }
+        if config.history_file.is_some() && !from_cli("history_file") {
+            self.history_file = config.history_file;
+        }
+        if config.jsonl_out.is_some() && !from_cli("jsonl_out") {
+            self.jsonl_out = config.jsonl_out;
+        }
+        if config.backfill_csv.is_some() && !from_cli("backfill_csv") {
+            self.backfill_csv = config.backfill_csv;
+        }
+        if config.fixture.is_some() && !from_cli("fixture") {
+            self.fixture = config.fixture;
+        }
+        if config.dump_raw.is_some() && !from_cli("dump_raw") {
+            self.dump_raw = config.dump_raw;
+        }
+        // 环境变量 SOLANA_RPC_URL 同样优先于配置文件
+        if let Some(rpc_url) = config.rpc_url {
+            if !from_cli("rpc_url") && std::env::var_os("SOLANA_RPC_URL").is_none() {
+                self.rpc_url = rpc_url;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 日志输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(anyhow::anyhow!(
+                "Unknown log format '{}', expected text or json",
+                other
+            )),
+        }
+    }
+}
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "raydium_tool")]
-pub enum Command {
-    Monitor {
-        /// 检查间隔（秒）
-        #[structopt(short, long, default_value = "30")]
-        interval: u64,
+pub struct Cli {
+    /// 日志格式 (text|json)
+    #[structopt(long, global = true, default_value = "text")]
+    log_format: LogFormat,
+
+    /// 日志级别 (trace|debug|info|warn|error)，未指定时读取 RUST_LOG
+    #[structopt(long, global = true)]
+    log_level: Option<tracing::Level>,
+
+    /// Raydium API 每秒最大请求数（0 表示不限流）
+    #[structopt(long, global = true, default_value = "5")]
+    api_rate: f64,
 
-        /// 显示前N个池子
+    /// Raydium API 请求使用的代理（http(s):// 或 socks5://），未指定时读取 HTTPS_PROXY/ALL_PROXY/HTTP_PROXY 环境变量
+    #[structopt(long, global = true)]
+    proxy: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+// 只在启动时解析一次，变体大小差异无关紧要
+#[allow(clippy::large_enum_variant)]
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    Monitor(MonitorOpts),
+    /// 向所有已配置的通知渠道发送一条测试警报，任一渠道失败时以非零状态退出
+    TestAlert(MonitorOpts),
+    /// 获取一次池子数据并输出后退出
+    Snapshot {
+        /// 显示前N个池子（0 表示全部）
         #[structopt(short, long, default_value = "20")]
         top_n: usize,
 
-        /// 价格变化警报阈值(%)
-        #[structopt(long, default_value = "1.0")]
-        price_alert: f64,
+        /// 输出格式 (text|json)
+        #[structopt(short, long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// 查询某个池子已保存的历史数据
+    History {
+        /// 池子ID
+        pool_id: String,
+
+        /// 查询最近N小时
+        #[structopt(long, default_value = "24")]
+        hours: i64,
+
+        /// 历史数据持久化文件
+        #[structopt(long, parse(from_os_str), default_value = "history.json")]
+        history_file: PathBuf,
+    },
+    /// 查看或修改关注列表
+    Watch {
+        /// 添加池子ID，可重复
+        #[structopt(long, number_of_values = 1)]
+        add: Vec<String>,
+
+        /// 移除池子ID，可重复
+        #[structopt(long, number_of_values = 1)]
+        remove: Vec<String>,
 
-        /// 交易量变化警报阈值(%)
-        #[structopt(long, default_value = "5.0")]
-        volume_alert: f64,
+        /// 关注列表文件（.json 保存为 JSON 数组，否则每行一个ID）
+        #[structopt(long, parse(from_os_str), default_value = "watchlist.txt")]
+        watchlist: PathBuf,
     },
 }
 
+// MonitorService 中的检查项名称
+const POOL_LIST_ITEM: &str = "pool-list";
+const MARKET_CAP_ITEM: &str = "market-caps";
+
+async fn run_monitor(mut opts: MonitorOpts) -> anyhow::Result<()> {
+    if let Some(path) = &opts.watchlist {
+        let ids = load_watchlist(path)?;
+        if ids.is_empty() {
+            anyhow::bail!("Watchlist {} is empty", path.display());
+        }
+        tracing::info!("Loaded {} pool ids from {}", ids.len(), path.display());
+        for id in ids {
+            if !opts.pool_ids.contains(&id) {
+                opts.pool_ids.push(id);
+            }
+        }
+    }
+    if !opts.compare.is_empty() {
+        if opts.compare.len() != 2 {
+            anyhow::bail!("--compare takes exactly two pool ids");
+        }
+        // 只监控指定池子时把对比的两个池子也加进去
+        if !opts.pool_ids.is_empty() {
+            for id in opts.compare.clone() {
+                if !opts.pool_ids.contains(&id) {
+                    opts.pool_ids.push(id);
+                }
+            }
+        }
+    }
+    let dump_raw = opts
+        .dump_raw
+        .as_ref()
+        .map(|dir| RawDump::new(dir, opts.dump_raw_gzip));
+    let source: Arc<dyn PoolDataSource> = match &opts.fixture {
+        Some(path) => {
+            tracing::info!(
+                "Using fixture {} instead of the Raydium API",
+                path.display()
+            );
+            Arc::new(FileSource::new(path))
+        }
+        None if !opts.pool_ids.is_empty() => Arc::new(IdsSource {
+            ids: opts.pool_ids.clone(),
+            dump_raw,
+        }),
+        None => Arc::new(HttpSource {
+            pages: opts.pages,
+            top_n: opts.top_n,
+            dump_raw,
+        }),
+    };
+    let mut builder = PoolMonitor::builder()
+        .capacity(opts.history_capacity)
+        .sol_price_max_deviation(opts.sol_price_max_deviation)
+        .supply_cache_ttl(Duration::from_secs(opts.supply_cache_ttl))
+        .momentum_weights(opts.momentum_weights);
+    if let Some(path) = &opts.history_file {
+        builder = builder.db_path(path);
+    }
+    let pool_monitor = Arc::new(builder.build().await?);
+    for target in &opts.price_targets {
+        pool_monitor.add_price_target(target.clone()).await;
+    }
+    for threshold in &opts.pool_thresholds {
+        pool_monitor.add_pool_threshold(threshold.clone()).await;
+    }
+    let display = DisplayOptions {
+        top_n: opts.top_n,
+        color: !opts.no_color && opts.color.enabled_for(&opts.output, std::env::var_os),
+        layout: opts.layout,
+        impact_size: opts.impact_size,
+    };
+    let thresholds = AlertThresholds {
+        price_alert: opts.price_alert,
+        volume_alert: opts.volume_alert,
+        tvl_drain_alert: opts.tvl_drain_alert,
+        volume_zscore_alert: opts.volume_zscore_alert,
+        reserve_alert: opts.reserve_alert,
+        min_burn_percent: opts.min_burn_percent,
+        alert_aggregate: opts.alert_aggregate,
+        alert_cooldown: chrono::Duration::seconds(opts.alert_cooldown as i64),
+        decouple_alert: opts.decouple_alert,
+    };
+    let anomaly_thresholds = AnomalyThresholds {
+        max_ratio: opts.anomaly_ratio,
+        min_pools: opts.anomaly_min_pools,
+    };
+    let notifiers = opts.notifiers()?;
+    if !opts.no_preflight {
+        preflight(&opts, &notifiers).await?;
+    }
+    let mut out = OutputSink::open(&opts.output, opts.output_truncate)?;
+    let mut archive = opts.jsonl_out.as_deref().map(SnapshotArchive::new);
+    if let Some(path) = &opts.backfill_csv {
+        pool_monitor.ingest_csv(path).await?;
+    }
+
+    // 价格轮询和市值轮询是 MonitorService 中两个独立间隔的检查项：
+    // - pool-list 按 --interval 获取池子列表，通过 channel 交给下面的主循环处理和显示；
+    //   主循环把本轮显示的池子写入 PoolMonitor::shown_pools
+    // - market-caps 按 --market-cap-interval 读取 shown_pools 查询市值，结果写入
+    //   PoolMonitor::market_caps；主循环每轮显示前读取缓存，两次查询之间沿用上一次的值
+    let poll_interval = opts.poll_interval()?;
+    let mut service = MonitorService::new();
+    // 获取失败也发送给主循环，由主循环决定退出还是继续
+    let (poll_tx, mut poll_rx) = mpsc::channel::<Result<PoolDataResult, String>>(1);
+    service
+        .add_item_with_warnings(POOL_LIST_ITEM, poll_interval, move || {
+            let source = source.clone();
+            let poll_tx = poll_tx.clone();
+            async move {
+                let fetch_start = std::time::Instant::now();
+                let fetched = source.fetch().await;
+                let fetch_time = fetch_start.elapsed();
+                let slow = fetch_time > poll_interval;
+                if slow {
+                    tracing::warn!(
+                        "Fetching pools took {}ms, longer than the {}ms interval; consider raising --interval",
+                        fetch_time.as_millis(),
+                        poll_interval.as_millis()
+                    );
+                }
+                // 没有获取到池子或获取超过轮询间隔时报告为警告，而不是错误
+                let (message, result) = match fetched {
+                    Ok(pool_data) => {
+                        let message = format!(
+                            "Fetched {} pools in {}ms",
+                            pool_data.pools.len(),
+                            fetch_time.as_millis()
+                        );
+                        let outcome = if pool_data.pools.is_empty() || slow {
+                            CheckOutcome::Warning(message)
+                        } else {
+                            CheckOutcome::Ok(message)
+                        };
+                        (Ok(outcome), Ok(pool_data))
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to check Raydium pools: {}", e);
+                        (Err(anyhow::anyhow!("{}", e)), Err(e.to_string()))
+                    }
+                };
+                poll_tx
+                    .send(result)
+                    .await
+                    .map_err(|_| anyhow::anyhow!("Monitor loop has stopped"))?;
+                message
+            }
+        })
+        .await;
+    let refresh_market_caps = opts.market_cap && opts.market_cap_mode == MarketCapMode::Rpc;
+    if refresh_market_caps {
+        let pool_monitor = pool_monitor.clone();
+        let rpc_client = Arc::new(RpcClient::new(opts.rpc_url.clone()));
+        let sol_price_cache = pool_monitor.sol_price_cache.clone();
+        let concurrency = opts.market_cap_concurrency;
+        service
+            .add_item(
+                MARKET_CAP_ITEM,
+                Duration::from_secs(opts.market_cap_interval.max(1)),
+                move || {
+                    let pool_monitor = pool_monitor.clone();
+                    let rpc_client = rpc_client.clone();
+                    let sol_price_cache = sol_price_cache.clone();
+                    async move {
+                        let updated = pool_monitor
+                            .refresh_market_caps(&rpc_client, &sol_price_cache, concurrency)
+                            .await;
+                        Ok(format!("Updated {} market caps", updated))
+                    }
+                },
+            )
+            .await;
+    }
+    // 没有订阅者时 MonitorService 会停止检查项，这里保持一个订阅
+    let _events = service.tx.subscribe();
+    service.run().await?;
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut pause_toggle = PauseToggle::new();
+    let mut polls = 0;
+    // 对比的两个池子当前是否处于脱钩状态，只在进入脱钩时报警
+    let mut decoupled = false;
+    let mut consecutive_failures = 0;
+    // 快照不完整时无法判断下架，只提示一次
+    let mut warned_incomplete = false;
+    loop {
+        if opts.count > 0 && polls >= opts.count {
+            tracing::info!("Reached poll count {}, exiting", opts.count);
+            service.stop().await;
+            return Ok(());
+        }
+        let fetched = tokio::select! {
+            fetched = poll_rx.recv() => match fetched {
+                Some(fetched) => fetched,
+                None => return Ok(()),
+            },
+            signal = &mut shutdown => {
+                tracing::info!("Received {}, shutting down", signal);
+                service.stop().await;
+                save_history(&pool_monitor).await;
+                return Ok(());
+            }
+            _ = pause_toggle.recv() => {
+                // 暂停期间不再请求 API，内存中的历史数据保持不变
+                if service.is_paused() {
+                    service.resume();
+                    tracing::info!("Resumed polling");
+                } else {
+                    service.pause();
+                    save_history(&pool_monitor).await;
+                    tracing::info!("Paused polling, send SIGUSR1 again to resume");
+                }
+                continue;
+            }
+        };
+        let mut pool_data = match fetched {
+            Ok(pool_data) => pool_data,
+            Err(e) if opts.fail_fast => {
+                service.stop().await;
+                save_history(&pool_monitor).await;
+                return Err(anyhow::anyhow!("Failed to check Raydium pools: {}", e));
+            }
+            Err(_) => {
+                consecutive_failures += 1;
+                if opts.failure_alert_after > 0
+                    && consecutive_failures % opts.failure_alert_after == 0
+                {
+                    let warning = format!(
+                        "{} consecutive fetch failures, the Raydium API may be down",
+                        consecutive_failures
+                    );
+                    tracing::error!("{}", warning);
+                    out.line(&format!("🚨 {}", warning));
+                }
+                continue;
+            }
+        };
+        if consecutive_failures > 0 {
+            tracing::info!(
+                "Recovered after {} consecutive fetch failures",
+                consecutive_failures
+            );
+            consecutive_failures = 0;
+        }
+        polls += 1;
+
+        // 上游返回明显错误的数据时本轮只显示和归档：不发出警报，
+        // 也不更新快照对比和历史数据，避免错误数据污染变化率的基准
+        let anomaly = pool_data.detect_anomaly(&anomaly_thresholds);
+        if let Some(reason) = &anomaly {
+            tracing::warn!("Skipping alerts for anomalous poll: {}", reason);
+            out.line(&format!(
+                "⚠️ Skipped anomalous data ({}), alerts paused for this poll",
+                reason
+            ));
+        }
+
+        if let Some(pool_type) = &opts.pool_type {
+            pool_data.retain_pools(opts.top_n, |pool_info| {
+                pool_info.matches_pool_type(pool_type)
+            });
+        }
+
+        let mut event_alerts = Vec::new();
+        if anomaly.is_none() {
+            // 上线、下架和目标价穿越属于警报：--quiet 时同样输出，并发送给通知渠道。
+            // 在按销毁比例、价格冲击和年龄过滤之前对比快照，
+            // 否则池子在过滤边界附近波动时会被反复误报为上线或下架
+            let new_pools = pool_monitor.new_pools(&pool_data).await;
+            event_alerts.extend(new_pool_alerts(&new_pools));
+            let new_pools = format_new_pools(&new_pools);
+            if !new_pools.is_empty() {
+                out.line(&new_pools);
+            }
+            let price_crossings = pool_monitor.check_price_targets(&pool_data).await;
+            event_alerts.extend(price_crossing_alerts(&price_crossings));
+            let crossings = format_price_crossings(&price_crossings);
+            if !crossings.is_empty() {
+                out.line(&crossings);
+            }
+            if !pool_data.complete && !warned_incomplete {
+                warned_incomplete = true;
+                tracing::warn!(
+                    "Fetched pools are only the first pages, delisting alerts are disabled; raise --pages to fetch all pools"
+                );
+            }
+            let disappeared = pool_monitor.disappeared_pools(&pool_data).await;
+            event_alerts.extend(disappeared_pool_alerts(&disappeared));
+            let disappeared = format_disappeared_pools(&disappeared);
+            if !disappeared.is_empty() {
+                out.line(&disappeared);
+            }
+            pool_monitor.record_snapshot(&pool_data).await;
+        }
+
+        if opts.min_burn_percent > 0.0 && opts.pool_ids.is_empty() {
+            pool_data.retain_pools(opts.top_n, |pool_info| {
+                pool_info.burn_percent >= opts.min_burn_percent
+            });
+        }
+
+        if let Some(max_slippage) = opts.max_slippage.filter(|_| opts.pool_ids.is_empty()) {
+            pool_data.retain_pools(opts.top_n, |pool_info| {
+                pool_info.price_impact(opts.impact_size) <= max_slippage
+            });
+        }
+
+        if opts.max_age.is_some() || opts.min_age.is_some() {
+            pool_data.retain_pools(opts.top_n, |pool_info| {
+                pool_info.age().is_some_and(|age| {
+                    opts.max_age.is_none_or(|max_age| age <= max_age)
+                        && opts.min_age.is_none_or(|min_age| age >= min_age)
+                })
+            });
+        }
+
+        if anomaly.is_none() {
+            for pool_info in &pool_data.pools {
+                pool_monitor.update_historical_data(pool_info).await;
+            }
+        }
+        if opts.memory_report_every > 0 && polls % opts.memory_report_every == 0 {
+            let stats = pool_monitor.memory_report().await;
+            tracing::info!(
+                pools = stats.pools,
+                records = stats.total_records,
+                min_per_pool = stats.min_records_per_pool,
+                max_per_pool = stats.max_records_per_pool,
+                avg_per_pool = format!("{:.1}", stats.avg_records_per_pool),
+                oldest = ?stats.oldest,
+                newest = ?stats.newest,
+                estimated_kb = stats.estimated_bytes / 1024,
+                "History memory report"
+            );
+        }
+
+        if opts.sort_by == SortBy::Momentum {
+            pool_monitor.sort_by_momentum(&mut pool_data.pools).await;
+        }
+
+        if opts.market_cap {
+            let shown = display_limit(opts.top_n).min(pool_data.pools.len());
+            match opts.market_cap_mode {
+                MarketCapMode::Rpc => {
+                    pool_monitor
+                        .set_shown_pools(&pool_data.pools[..shown])
+                        .await;
+                    // 市值轮询的第一次检查发生在还没有池子数据时，这里补查一次
+                    if polls == 1 {
+                        service.check_once(MARKET_CAP_ITEM).await;
+                    }
+                    pool_monitor
+                        .apply_market_caps(&mut pool_data.pools[..shown])
+                        .await;
+                }
+                MarketCapMode::Fdv => {
+                    for pool_info in &mut pool_data.pools[..shown] {
+                        pool_info.market_cap = pool_info.fdv_estimate;
+                    }
+                }
+            }
+        }
+
+        if let Some(archive) = archive.as_mut() {
+            if let Err(e) = archive.append(&pool_data) {
+                tracing::error!("Failed to archive snapshot: {}", e);
+            }
+        }
+
+        let (output, mut alerts) = format_pool_data(
+            &pool_data,
+            &pool_monitor,
+            &display,
+            &thresholds,
+            anomaly.is_none(),
+        )
+        .await;
+        // 先算出解耦警报，使 --quiet 的输出和 "no alerts" 判断包含它
+        let mut correlation_line = None;
+        if let [pool_a, pool_b] = opts.compare.as_slice() {
+            let correlation = pool_monitor
+                .correlation(pool_a, pool_b, opts.correlation_window)
+                .await;
+            let pair_name = |id: &str| {
+                pool_data
+                    .pools
+                    .iter()
+                    .find(|pool_info| pool_info.id == id)
+                    .map(|pool_info| pool_info.pair_name())
+                    .unwrap_or_else(|| id.to_string())
+            };
+            let is_decoupled =
+                correlation.is_some_and(|correlation| correlation < opts.decouple_alert);
+            correlation_line = Some(format!(
+                "🔗 Correlation {} vs {} ({}): {}{}",
+                pair_name(pool_a),
+                pair_name(pool_b),
+                format_age(opts.correlation_window),
+                correlation.map_or("n/a".to_string(), |c| format!("{:.2}", c)),
+                if is_decoupled {
+                    " ⚠️ decoupled"
+                } else {
+                    ""
+                }
+            ));
+            // 异常数据不改变解耦状态，也不报警
+            if anomaly.is_none() {
+                if is_decoupled && !decoupled {
+                    alerts.push(Alert {
+                        pool_id: format!("{},{}", pool_a, pool_b),
+                        symbol_a: pair_name(pool_a),
+                        symbol_b: pair_name(pool_b),
+                        metric: AlertKind::Decoupling,
+                        value: correlation.unwrap_or_default(),
+                        threshold: opts.decouple_alert,
+                        summary: None,
+                    });
+                }
+                decoupled = is_decoupled;
+            }
+        }
+
+        if !opts.quiet && (opts.top_gainers > 0 || opts.top_losers > 0) {
+            let movers = pool_monitor
+                .top_movers(
+                    &pool_data.pools,
+                    opts.top_gainers,
+                    opts.top_losers,
+                    opts.movers_min_volume,
+                )
+                .await;
+            out.line(&format_top_movers(
+                &movers,
+                pool_data.timestamp,
+                opts.price_alert,
+                display.color,
+            ));
+        } else if !opts.quiet {
+            out.line(&output);
+        } else if alerts.is_empty() && event_alerts.is_empty() {
+            out.line(&format!(
+                "no alerts at {}",
+                pool_data.timestamp.format("%H:%M:%S")
+            ));
+        } else {
+            for alert in &alerts {
+                out.line(&alert.to_string());
+            }
+        }
+        if let Some(correlation_line) = correlation_line.filter(|_| !opts.quiet) {
+            out.line(&correlation_line);
+        }
+        let alerts = [event_alerts, alerts].concat();
+        if !notifiers.is_empty() {
+            notifiers.notify_all(&alerts).await;
+        }
+
+        save_history(&pool_monitor).await;
+    }
+}
+
+// 启动自检：逐项输出 ✓/✗，必需的依赖不可用时返回错误（--resilient 时只警告并继续）。
+// Raydium API 总是必需；开启 RPC 市值计算时 SOL 价格和 RPC 节点也是必需的；通知渠道失败只提示
+async fn preflight(opts: &MonitorOpts, notifiers: &Notifiers) -> anyhow::Result<()> {
+    let rpc_market_caps = opts.market_cap && opts.market_cap_mode == MarketCapMode::Rpc;
+    let mut checks: Vec<(String, bool, anyhow::Result<()>)> = Vec::new();
+
+    if opts.fixture.is_none() {
+        let result = fetch_raydium_data(1).await.map(|_| ());
+        checks.push(("Raydium API".to_string(), true, result.map_err(Into::into)));
+    }
+    // 离线模式（--fixture）下只有计算市值时才需要访问网络
+    if opts.fixture.is_none() || rpc_market_caps {
+        let result = get_sol_price().await.map(|_| ());
+        checks.push((
+            "SOL price".to_string(),
+            rpc_market_caps,
+            result.map_err(Into::into),
+        ));
+    }
+    if rpc_market_caps {
+        let result = RpcClient::new(opts.rpc_url.clone()).get_version().await;
+        checks.push((
+            format!("Solana RPC {}", opts.rpc_url),
+            true,
+            result.map(|_| ()).map_err(Into::into),
+        ));
+    }
+    for (name, result) in notifiers.check_each().await {
+        checks.push((format!("Notifier {}", name), false, result));
+    }
+
+    if checks.is_empty() {
+        return Ok(());
+    }
+    let mut failed_required = Vec::new();
+    println!("Preflight:");
+    for (name, required, result) in &checks {
+        match result {
+            Ok(()) => println!("  ✓ {}", name),
+            Err(e) => {
+                println!("  ✗ {}: {}", name, e);
+                if *required {
+                    failed_required.push(name.as_str());
+                }
+            }
+        }
+    }
+    if failed_required.is_empty() {
+        return Ok(());
+    }
+    if opts.resilient {
+        tracing::warn!(
+            "Preflight failed for {}, continuing because of --resilient",
+            failed_required.join(", ")
+        );
+        return Ok(());
+    }
+    anyhow::bail!(
+        "Preflight failed for {} (use --resilient to start anyway or --no-preflight to skip)",
+        failed_required.join(", ")
+    )
+}
+
+// 用一条虚构的警报检查通知渠道的配置
+async fn send_test_alert(opts: &MonitorOpts) -> anyhow::Result<()> {
+    let notifiers = opts.notifiers()?;
+    if notifiers.is_empty() {
+        anyhow::bail!("No notifiers configured");
+    }
+    let alert = Alert {
+        pool_id: "TEST".to_string(),
+        symbol_a: "WSOL".to_string(),
+        symbol_b: "TEST".to_string(),
+        metric: AlertKind::Price,
+        value: opts.price_alert * 2.0,
+        threshold: opts.price_alert,
+        summary: None,
+    };
+
+    let mut failed = 0;
+    for (name, result) in notifiers.notify_each(&[alert]).await {
+        match result {
+            Ok(()) => println!("✅ {}: test alert sent", name),
+            Err(e) => {
+                failed += 1;
+                println!("❌ {}: {}", name, e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} notifiers failed", failed, notifiers.len());
+    }
+    Ok(())
+}
+
+async fn save_history(pool_monitor: &PoolMonitor) {
+    if let Err(e) = pool_monitor.save().await {
+        tracing::error!("Failed to save historical data: {}", e);
+    }
+}
+
+// 等待 SIGINT(Ctrl-C) 或 SIGTERM，返回触发的信号名
+#[cfg(unix)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (Ok(mut interrupt), Ok(mut terminate)) = (
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::terminate()),
+    ) else {
+        tracing::warn!("Failed to install signal handlers, falling back to Ctrl-C");
+        let _ = tokio::signal::ctrl_c().await;
+        return "SIGINT";
+    };
+    tokio::select! {
+        _ = interrupt.recv() => "SIGINT",
+        _ = terminate.recv() => "SIGTERM",
+    }
+}
+
+// 收到 SIGUSR1 时切换暂停/恢复
+#[cfg(unix)]
+struct PauseToggle(Option<tokio::signal::unix::Signal>);
+
+#[cfg(unix)]
+impl PauseToggle {
+    fn new() -> Self {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let signal = signal(SignalKind::user_defined1())
+            .inspect_err(|e| tracing::warn!("Failed to install SIGUSR1 handler: {}", e))
+            .ok();
+        PauseToggle(signal)
+    }
+
+    async fn recv(&mut self) {
+        match self.0.as_mut() {
+            Some(signal) => {
+                signal.recv().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+// 非 Unix 平台不支持暂停
+#[cfg(not(unix))]
+struct PauseToggle;
+
+#[cfg(not(unix))]
+impl PauseToggle {
+    fn new() -> Self {
+        PauseToggle
+    }
+
+    async fn recv(&mut self) {
+        std::future::pending().await
+    }
+}
+
+// 非 Unix 平台只支持 Ctrl-C
+#[cfg(not(unix))]
+async fn shutdown_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "Ctrl-C"
+}
+
+// 解析 30s、15m、24h、7d 形式的时长
+fn parse_age(s: &str) -> anyhow::Result<chrono::Duration> {
+    let s = s.trim();
+    let split = s.len() - s.chars().last().map_or(0, |c| c.len_utf8());
+    let (value, unit) = s.split_at(split);
+    let value: i64 = value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid age '{}', expected e.g. 30m, 24h or 7d", s))?;
+    match unit {
+        "s" => Ok(chrono::Duration::seconds(value)),
+        "m" => Ok(chrono::Duration::minutes(value)),
+        "h" => Ok(chrono::Duration::hours(value)),
+        "d" => Ok(chrono::Duration::days(value)),
+        _ => Err(anyhow::anyhow!(
+            "Invalid age '{}', expected e.g. 30m, 24h or 7d",
+            s
+        )),
+    }
+}
+
+// top_n 的上限，0 表示不限制
+const MAX_TOP_N: usize = 1000;
+
+fn validate_top_n(top_n: usize) -> anyhow::Result<()> {
+    if top_n > MAX_TOP_N {
+        return Err(anyhow::anyhow!(
+            "top_n must be between 0 (all) and {}, got {}",
+            MAX_TOP_N,
+            top_n
+        ));
+    }
+    Ok(())
+}
+
+fn init_logging(format: LogFormat, level: Option<tracing::Level>) {
+    // 优先使用 --log-level，其次 RUST_LOG，默认 info
+    let filter = match level {
+        Some(level) => EnvFilter::default().add_directive(LevelFilter::from_level(level).into()),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::Builder::new()
-        .filter_level(LevelFilter::Info)
-        .init();
-
-    let command = Command::from_args();
-
-    match command {
-        Command::Monitor {
-            interval: _,
-            top_n: _,
-            price_alert: _,
-            volume_alert: _,
-        } => {}
+    let matches = Cli::clap().get_matches();
+    let cli = Cli::from_clap(&matches);
+    init_logging(cli.log_format, cli.log_level);
+    client::init_raydium_client(cli.api_rate, cli.proxy.as_deref())?;
+
+    match cli.command {
+        Command::Monitor(mut opts) => {
+            opts.load_config(&matches, "monitor")?;
+            validate_top_n(opts.top_n)?;
+            run_monitor(opts).await?;
+        }
+        Command::TestAlert(mut opts) => {
+            opts.load_config(&matches, "test-alert")?;
+            send_test_alert(&opts).await?;
+        }
+        Command::Snapshot { top_n, format } => {
+            validate_top_n(top_n)?;
+            let pool_data = check_raydium_pools_pages(1, top_n).await?;
+            let output = match format {
+                OutputFormat::Text => format_snapshot_text(&pool_data, top_n),
+                OutputFormat::Json => format_snapshot_json(&pool_data, top_n)?,
+            };
+            println!("{}", output);
+        }
+        Command::History {
+            pool_id,
+            hours,
+            history_file,
+        } => {
+            let pool_monitor = PoolMonitor::new();
+            pool_monitor.load_history(&history_file).await?;
+            let records = pool_monitor.history_since(&pool_id, hours).await?;
+            println!("{}", format_history_table(&pool_id, &records));
+        }
+        Command::Watch {
+            add,
+            remove,
+            watchlist,
+        } => {
+            let ids = if add.is_empty() && remove.is_empty() {
+                load_watchlist(&watchlist)?
+            } else {
+                update_watchlist(&watchlist, &add, &remove)?
+            };
+            for id in ids {
+                println!("{}", id);
+            }
+        }
     }
     Ok(())
 }
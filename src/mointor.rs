@@ -1,17 +1,24 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use futures::future::join_all;
-use log::info;
+use futures::stream::{self, Stream};
+use futures::TryFutureExt;
+use rand::Rng;
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::{
-    sync::{broadcast, mpsc, Mutex},
-    time::{self, Duration, Instant},
+    sync::{broadcast, broadcast::error::RecvError, mpsc, Mutex},
+    time::{self, Duration, Instant, MissedTickBehavior},
 };
+use tracing::info;
 
 #[derive(Debug, Clone)]
 pub enum MonitorStatus {
+    // 已注册但尚未完成第一次检查
+    Unknown,
     OK(String),
     Warning(String),
     Error(String),
@@ -21,29 +28,134 @@ pub enum MonitorStatus {
 pub struct MonitorEvent {
     pub item_name: String,
     pub status: MonitorStatus,
-    pub timestamp: Instant,
+    // 使用墙上时间，便于跨进程比较与序列化
+    pub timestamp: DateTime<Utc>,
 }
 
+// 检查函数的成功结果，Warning 表示异常但尚不构成错误（例如 TVL 过低）
+#[derive(Debug, Clone)]
+pub enum CheckOutcome {
+    Ok(String),
+    Warning(String),
+}
+
+type CheckFn =
+    Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<CheckOutcome>> + Send>> + Send + Sync>;
+
 #[derive(Clone)]
 pub struct MonitorItem {
     name: String,
     check_interval: Duration,
     // 修改函数类型为返回 Future 的函数
-    check_fn: Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<String>> + Send>> + Send + Sync>,
+    check_fn: CheckFn,
+    // 每次检查的最大随机延迟；设置后首次检查也会在间隔内随机错开
+    jitter: Option<Duration>,
+}
+
+// [0, max) 范围内的随机时长
+fn random_duration(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    Duration::from_nanos(rand::thread_rng().gen_range(0..max.as_nanos() as u64))
+}
+
+// 执行一次检查并更新指标
+async fn run_check(
+    item: &MonitorItem,
+    metrics: &Mutex<HashMap<String, MonitorMetrics>>,
+) -> MonitorStatus {
+    // Instant 仅用于内部计时
+    let start = Instant::now();
+
+    // 执行异步检查函数
+    let check_future = (item.check_fn)();
+    let result = check_future.await;
+
+    let status = match result {
+        Ok(CheckOutcome::Ok(message)) => MonitorStatus::OK(message),
+        Ok(CheckOutcome::Warning(message)) => MonitorStatus::Warning(message),
+        Err(e) => MonitorStatus::Error(e.to_string()),
+    };
+
+    let mut metrics = metrics.lock().await;
+    let metric = metrics.entry(item.name.clone()).or_insert(MonitorMetrics {
+        last_check_time: start,
+        last_success_time: None,
+        last_status: status.clone(),
+        check_count: 0,
+        warning_count: 0,
+        error_count: 0,
+    });
+
+    metric.last_check_time = start;
+    metric.last_status = status.clone();
+    metric.check_count += 1;
+
+    match status {
+        MonitorStatus::Error(_) => metric.error_count += 1,
+        MonitorStatus::Warning(_) => {
+            metric.warning_count += 1;
+            metric.last_success_time = Some(start);
+        }
+        _ => metric.last_success_time = Some(start),
+    }
+
+    status
 }
 
 pub struct MonitorMetrics {
     last_check_time: Instant,
+    last_success_time: Option<Instant>,
     last_status: MonitorStatus,
     check_count: u64,
+    warning_count: u64,
     error_count: u64,
 }
 
+// 服务整体健康状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    // 所有检查项都没有出错
+    Healthy,
+    // 至少一个检查项出现过错误
+    Degraded,
+    // 所有检查项最近一次检查都失败
+    Down,
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemHealth {
+    pub name: String,
+    // 尚未执行过检查时为 None
+    pub last_status: Option<MonitorStatus>,
+    pub check_count: u64,
+    pub warning_count: u64,
+    pub error_count: u64,
+    pub error_rate: f64,
+    // 距离上一次成功检查的时间，从未成功时为 None
+    pub since_last_success: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceHealth {
+    pub status: HealthStatus,
+    pub items: Vec<ItemHealth>,
+}
+
 pub struct MonitorService {
     items: Arc<Mutex<Vec<MonitorItem>>>,
     metrics: Arc<Mutex<HashMap<String, MonitorMetrics>>>,
     pub tx: broadcast::Sender<MonitorEvent>,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    // 暂停时检查项照常计时但跳过检查，已有的状态和指标保持不变
+    paused: Arc<AtomicBool>,
+}
+
+impl Default for MonitorService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MonitorService {
@@ -54,6 +166,7 @@ impl MonitorService {
             metrics: Arc::new(Mutex::new(HashMap::new())),
             tx,
             shutdown_tx: None,
+            paused: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -63,12 +176,92 @@ impl MonitorService {
         F: Fn() -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Result<String>> + Send + 'static,
     {
-        let mut items = self.items.lock().await;
-        items.push(MonitorItem {
+        self.register(MonitorItem {
+            name: name.to_string(),
+            check_interval: interval,
+            check_fn: Arc::new(move || Box::pin(check_fn().map_ok(CheckOutcome::Ok))),
+            jitter: None,
+        })
+        .await;
+    }
+
+    // 检查函数可以返回 CheckOutcome::Warning，用于报告非致命的异常
+    pub async fn add_item_with_warnings<F, Fut>(&self, name: &str, interval: Duration, check_fn: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<CheckOutcome>> + Send + 'static,
+    {
+        self.register(MonitorItem {
             name: name.to_string(),
             check_interval: interval,
             check_fn: Arc::new(move || Box::pin(check_fn())),
+            jitter: None,
+        })
+        .await;
+    }
+
+    // 带随机抖动的检查项，避免相同间隔的检查项同时请求 API
+    pub async fn add_item_with_jitter<F, Fut>(
+        &self,
+        name: &str,
+        interval: Duration,
+        max_jitter: Duration,
+        check_fn: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        self.register(MonitorItem {
+            name: name.to_string(),
+            check_interval: interval,
+            check_fn: Arc::new(move || Box::pin(check_fn().map_ok(CheckOutcome::Ok))),
+            jitter: Some(max_jitter),
+        })
+        .await;
+    }
+
+    // 带状态的检查项：状态随检查项保存，每次检查时传入，便于与上一次观测值比较
+    pub async fn add_stateful_item<S, F, Fut>(
+        &self,
+        name: &str,
+        interval: Duration,
+        state: S,
+        check_fn: F,
+    ) where
+        S: Send + 'static,
+        F: Fn(Arc<Mutex<S>>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(state));
+        self.register(MonitorItem {
+            name: name.to_string(),
+            check_interval: interval,
+            check_fn: Arc::new(move || Box::pin(check_fn(state.clone()).map_ok(CheckOutcome::Ok))),
+            jitter: None,
+        })
+        .await;
+    }
+
+    // 注册检查项，初始化为 Unknown 状态并发送初始事件，订阅者在首次检查前即可看到该项
+    async fn register(&self, item: MonitorItem) {
+        self.metrics.lock().await.insert(
+            item.name.clone(),
+            MonitorMetrics {
+                last_check_time: Instant::now(),
+                last_success_time: None,
+                last_status: MonitorStatus::Unknown,
+                check_count: 0,
+                warning_count: 0,
+                error_count: 0,
+            },
+        );
+        // 没有订阅者时发送失败，忽略即可
+        let _ = self.tx.send(MonitorEvent {
+            item_name: item.name.clone(),
+            status: MonitorStatus::Unknown,
+            timestamp: Utc::now(),
         });
+        self.items.lock().await.push(item);
     }
 
     pub async fn run(&mut self) -> Result<()> {
@@ -78,6 +271,7 @@ impl MonitorService {
         let items = self.items.clone();
         let tx = self.tx.clone();
         let metrics = self.metrics.clone();
+        let paused = self.paused.clone();
 
         tokio::spawn(async move {
             let mut handles = vec![];
@@ -87,43 +281,49 @@ impl MonitorService {
                 let item = item.clone();
                 let tx = tx.clone();
                 let metrics = metrics.clone();
+                let paused = paused.clone();
 
                 let handle = tokio::spawn(async move {
-                    let mut interval = time::interval(item.check_interval);
+                    let mut interval = match item.jitter {
+                        // 首次检查在一个间隔内随机错开
+                        Some(_) => time::interval_at(
+                            Instant::now() + random_duration(item.check_interval),
+                            item.check_interval,
+                        ),
+                        None => time::interval(item.check_interval),
+                    };
+                    // 检查超时时跳过错过的 tick，避免请求堆积
+                    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    let mut last_tick: Option<Instant> = None;
                     loop {
-                        interval.tick().await;
-                        let start = Instant::now();
-
-                        // 执行异步检查函数
-                        let check_future = (item.check_fn)();
-                        let result = check_future.await;
-
-                        let status = match result {
-                            Ok(message) => MonitorStatus::OK(message),
-                            Err(e) => MonitorStatus::Error(e.to_string()),
-                        };
-
-                        let mut metrics = metrics.lock().await;
-                        let metric = metrics.entry(item.name.clone()).or_insert(MonitorMetrics {
-                            last_check_time: start,
-                            last_status: status.clone(),
-                            check_count: 0,
-                            error_count: 0,
-                        });
-
-                        metric.last_check_time = start;
-                        metric.last_status = status.clone();
-                        metric.check_count += 1;
-
-                        if matches!(status, MonitorStatus::Error(_)) {
-                            metric.error_count += 1;
+                        let tick = interval.tick().await;
+                        if let Some(last_tick) = last_tick {
+                            let skipped = (tick - last_tick).as_millis()
+                                / item.check_interval.as_millis().max(1);
+                            if skipped > 1 {
+                                tracing::warn!(
+                                    "Check '{}' overran its interval, skipped {} tick(s)",
+                                    item.name,
+                                    skipped - 1
+                                );
+                            }
+                        }
+                        last_tick = Some(tick);
+                        if paused.load(Ordering::Relaxed) {
+                            continue;
                         }
+                        if let Some(max_jitter) = item.jitter {
+                            time::sleep(random_duration(max_jitter)).await;
+                        }
+                        // 事件使用 Utc 时间戳
+                        let checked_at = Utc::now();
+                        let status = run_check(&item, &metrics).await;
 
                         if tx
                             .send(MonitorEvent {
                                 item_name: item.name.clone(),
                                 status,
-                                timestamp: start,
+                                timestamp: checked_at,
                             })
                             .is_err()
                         {
@@ -141,6 +341,100 @@ impl MonitorService {
         Ok(())
     }
 
+    // 只包含指定检查项的事件流；接收太慢丢失事件时记录警告并继续，服务的发送端关闭后结束
+    pub fn subscribe_filtered(&self, name: &str) -> impl Stream<Item = MonitorEvent> + Send {
+        let name = name.to_string();
+        stream::unfold(self.tx.subscribe(), move |mut rx| {
+            let name = name.clone();
+            async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(event) if event.item_name == name => return Some((event, rx)),
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(skipped)) => {
+                            tracing::warn!(
+                                "Subscriber for '{}' lagged behind, skipped {} event(s)",
+                                name,
+                                skipped
+                            );
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })
+    }
+
+    // 立即执行一次指定检查项（不需要启动 run），未注册时返回 None
+    pub async fn check_once(&self, name: &str) -> Option<MonitorStatus> {
+        let item = {
+            let items = self.items.lock().await;
+            items.iter().find(|item| item.name == name)?.clone()
+        };
+        Some(run_check(&item, &self.metrics).await)
+    }
+
+    // 汇总所有检查项的健康状态
+    pub async fn health(&self) -> ServiceHealth {
+        let items = self.items.lock().await;
+        let metrics = self.metrics.lock().await;
+
+        let items: Vec<ItemHealth> = items
+            .iter()
+            .map(|item| match metrics.get(&item.name) {
+                Some(metric) => ItemHealth {
+                    name: item.name.clone(),
+                    last_status: Some(metric.last_status.clone()),
+                    check_count: metric.check_count,
+                    warning_count: metric.warning_count,
+                    error_count: metric.error_count,
+                    error_rate: if metric.check_count > 0 {
+                        metric.error_count as f64 / metric.check_count as f64
+                    } else {
+                        0.0
+                    },
+                    since_last_success: metric.last_success_time.map(|t| t.elapsed()),
+                },
+                None => ItemHealth {
+                    name: item.name.clone(),
+                    last_status: None,
+                    check_count: 0,
+                    warning_count: 0,
+                    error_count: 0,
+                    error_rate: 0.0,
+                    since_last_success: None,
+                },
+            })
+            .collect();
+
+        let checked: Vec<&ItemHealth> = items.iter().filter(|i| i.check_count > 0).collect();
+        let status = if !checked.is_empty()
+            && checked
+                .iter()
+                .all(|i| matches!(i.last_status, Some(MonitorStatus::Error(_))))
+        {
+            HealthStatus::Down
+        } else if checked.iter().any(|i| i.error_rate > 0.0) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+
+        ServiceHealth { status, items }
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
     pub async fn stop(&mut self) {
         if let Some(tx) = self.shutdown_tx.take() {
             let _ = tx.send(()).await;
@@ -148,3 +442,180 @@ impl MonitorService {
         info!("Monitor service stopped");
     }
 }
+
+#[test]
+fn test_check_once_runs_registered_item() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let service = MonitorService::new();
+        service
+            .add_item("api", Duration::from_secs(60), || async {
+                Ok("fine".to_string())
+            })
+            .await;
+
+        let status = service.check_once("api").await;
+        assert!(matches!(status, Some(MonitorStatus::OK(message)) if message == "fine"));
+        {
+            let metrics = service.metrics.lock().await;
+            let metric = &metrics["api"];
+            assert_eq!(metric.check_count, 1);
+            assert_eq!(metric.error_count, 0);
+            assert!(metric.last_success_time.is_some());
+            assert!(matches!(metric.last_status, MonitorStatus::OK(_)));
+        }
+
+        // 未注册的检查项
+        assert!(service.check_once("missing").await.is_none());
+    });
+}
+
+#[test]
+fn test_health_rollup_from_check_results() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let service = MonitorService::new();
+        let failing = Arc::new(AtomicBool::new(true));
+        service
+            .add_item("ok", Duration::from_secs(60), || async {
+                Ok("fine".to_string())
+            })
+            .await;
+        let flag = failing.clone();
+        service
+            .add_item("flaky", Duration::from_secs(60), move || {
+                let failing = flag.load(Ordering::Relaxed);
+                async move {
+                    if failing {
+                        Err(anyhow::anyhow!("boom"))
+                    } else {
+                        Ok("recovered".to_string())
+                    }
+                }
+            })
+            .await;
+
+        // 还没有执行过任何检查
+        assert_eq!(service.health().await.status, HealthStatus::Healthy);
+
+        service.check_once("ok").await;
+        assert_eq!(service.health().await.status, HealthStatus::Healthy);
+
+        // 部分检查项出错
+        service.check_once("flaky").await;
+        assert_eq!(service.health().await.status, HealthStatus::Degraded);
+
+        // 恢复后仍因出现过错误而保持 Degraded
+        failing.store(false, Ordering::Relaxed);
+        service.check_once("flaky").await;
+        let health = service.health().await;
+        assert_eq!(health.status, HealthStatus::Degraded);
+        let flaky = health
+            .items
+            .iter()
+            .find(|item| item.name == "flaky")
+            .unwrap();
+        assert_eq!(flaky.error_rate, 0.5);
+
+        // 所有执行过的检查项最近一次都失败，未执行过的检查项不计入
+        let service = MonitorService::new();
+        service
+            .add_item("down", Duration::from_secs(60), || async {
+                Err(anyhow::anyhow!("boom"))
+            })
+            .await;
+        service
+            .add_item("idle", Duration::from_secs(60), || async {
+                Ok("fine".to_string())
+            })
+            .await;
+        service.check_once("down").await;
+        assert_eq!(service.health().await.status, HealthStatus::Down);
+    });
+}
+
+#[test]
+fn test_warning_outcome_counts_as_success() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let service = MonitorService::new();
+        service
+            .add_item_with_warnings("pools", Duration::from_secs(60), || async {
+                Ok(CheckOutcome::Warning("TVL below $1K".to_string()))
+            })
+            .await;
+
+        let status = service.check_once("pools").await;
+        assert!(
+            matches!(status, Some(MonitorStatus::Warning(message)) if message == "TVL below $1K")
+        );
+        let metrics = service.metrics.lock().await;
+        let metric = &metrics["pools"];
+        assert_eq!(metric.warning_count, 1);
+        assert_eq!(metric.error_count, 0);
+        assert!(metric.last_success_time.is_some());
+    });
+}
+
+#[test]
+fn test_subscribe_filtered_skips_lagged_events() {
+    use futures::StreamExt;
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let service = MonitorService::new();
+        let mut events = Box::pin(service.subscribe_filtered("pools"));
+        let event = |name: &str, index: usize| MonitorEvent {
+            item_name: name.to_string(),
+            status: MonitorStatus::OK(index.to_string()),
+            timestamp: Utc::now(),
+        };
+        // 发送超过通道容量(100)的事件，订阅者会先收到 Lagged
+        for index in 0..300 {
+            let name = if index % 3 == 0 {
+                "pools"
+            } else {
+                "market-caps"
+            };
+            service.tx.send(event(name, index)).unwrap();
+        }
+
+        let mut received = 0;
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(100), events.next()).await
+        {
+            assert_eq!(event.item_name, "pools");
+            received += 1;
+        }
+        // 丢失了最早的事件，但流没有因 Lagged 结束
+        assert!(received > 0 && received < 100);
+
+        service.tx.send(event("pools", 300)).unwrap();
+        assert!(events.next().await.is_some());
+    });
+}
+
+#[test]
+fn test_stateful_item_keeps_state_between_checks() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let service = MonitorService::new();
+        service
+            .add_stateful_item(
+                "counter",
+                Duration::from_secs(60),
+                0u32,
+                |count: Arc<Mutex<u32>>| async move {
+                    let mut count = count.lock().await;
+                    *count += 1;
+                    Ok(format!("check #{}", count))
+                },
+            )
+            .await;
+
+        let first = service.check_once("counter").await;
+        let second = service.check_once("counter").await;
+        assert!(matches!(first, Some(MonitorStatus::OK(message)) if message == "check #1"));
+        assert!(matches!(second, Some(MonitorStatus::OK(message)) if message == "check #2"));
+    });
+}
@@ -0,0 +1,426 @@
+use crate::raydium_pool::{format_price, AlertKind};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::join_all;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde_json::json;
+use std::fmt;
+
+// 发送给通知渠道的警报
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub pool_id: String,
+    pub symbol_a: String,
+    pub symbol_b: String,
+    pub metric: AlertKind,
+    // 触发时的变化率(%)或 z-score
+    pub value: f64,
+    pub threshold: f64,
+    // 同类警报过多时合并成的一条汇总（数量和变化最大的前3个池子），设置时直接作为显示内容
+    pub summary: Option<String>,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(summary) = &self.summary {
+            return write!(f, "{}", summary);
+        }
+        let metric = match self.metric {
+            AlertKind::Price => "price change",
+            AlertKind::Volume => "volume change",
+            AlertKind::TvlDrain => "TVL drain",
+            AlertKind::VolumeSpike => "volume z-score",
+            AlertKind::ReserveShift => "reserve shift",
+            AlertKind::Decoupling => "price correlation",
+            // 事件类警报没有变化率和阈值，单独描述
+            AlertKind::NewPool => {
+                return write!(
+                    f,
+                    "[{}/{}] new pool listed at price {} pool {}",
+                    self.symbol_a,
+                    self.symbol_b,
+                    format_price(self.value),
+                    self.pool_id
+                );
+            }
+            AlertKind::PoolDelisted => {
+                return write!(f, "pool {} no longer listed", self.pool_id);
+            }
+            AlertKind::PriceTarget => {
+                return write!(
+                    f,
+                    "[{}/{}] price {} target {}: now {} pool {}",
+                    self.symbol_a,
+                    self.symbol_b,
+                    if self.value > self.threshold {
+                        "rose above"
+                    } else {
+                        "fell below"
+                    },
+                    format_price(self.threshold),
+                    format_price(self.value),
+                    self.pool_id
+                );
+            }
+        };
+        let unit = match self.metric {
+            AlertKind::VolumeSpike | AlertKind::Decoupling => "",
+            _ => "%",
+        };
+        write!(
+            f,
+            "[{}/{}] {} {:.2}{} (threshold {:.2}{}) pool {}",
+            self.symbol_a,
+            self.symbol_b,
+            metric,
+            self.value,
+            unit,
+            self.threshold,
+            unit,
+            self.pool_id
+        )
+    }
+}
+
+// 警报通知渠道，新增渠道只需实现该 trait
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    // 渠道名称，用于日志
+    fn name(&self) -> &str;
+
+    async fn notify(&self, alert: &Alert) -> Result<()>;
+
+    // 启动自检：验证配置和连通性，不发送消息；默认不检查
+    async fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // 一轮轮询的所有警报，默认逐条发送；需要合并发送的渠道可以覆盖
+    async fn notify_batch(&self, alerts: &[Alert]) -> Result<()> {
+        for alert in alerts {
+            self.notify(alert).await?;
+        }
+        Ok(())
+    }
+}
+
+// 所有已注册的通知渠道，每轮的警报并发发送到全部渠道
+#[derive(Default)]
+pub struct Notifiers {
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl Notifiers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, notifier: Box<dyn Notifier>) {
+        self.notifiers.push(notifier);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.notifiers.len()
+    }
+
+    // 并发发送到全部渠道，返回每个渠道的名称和结果
+    pub async fn notify_each(&self, alerts: &[Alert]) -> Vec<(String, Result<()>)> {
+        join_all(self.notifiers.iter().map(|notifier| async move {
+            (
+                notifier.name().to_string(),
+                notifier.notify_batch(alerts).await,
+            )
+        }))
+        .await
+    }
+
+    // 并发自检全部渠道，返回每个渠道的名称和结果
+    pub async fn check_each(&self) -> Vec<(String, Result<()>)> {
+        join_all(
+            self.notifiers.iter().map(|notifier| async move {
+                (notifier.name().to_string(), notifier.check().await)
+            }),
+        )
+        .await
+    }
+
+    // 某个渠道失败只记录日志，不影响其他渠道
+    pub async fn notify_all(&self, alerts: &[Alert]) {
+        if alerts.is_empty() {
+            return;
+        }
+        for (name, result) in self.notify_each(alerts).await {
+            if let Err(e) = result {
+                tracing::warn!("Failed to send alert via {}: {}", name, e);
+            }
+        }
+    }
+}
+
+// 发送请求并检查状态码。bot token 和 webhook 地址都是密钥，
+// 而 reqwest 的错误信息包含完整 URL，返回前去掉 URL，避免写入日志或输出
+async fn send_without_url(request: reqwest::RequestBuilder) -> Result<()> {
+    request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.without_url())?;
+    Ok(())
+}
+
+// 每条警报输出一行到标准输出，便于管道处理
+pub struct StdoutNotifier;
+
+#[async_trait]
+impl Notifier for StdoutNotifier {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        println!("ALERT {}", alert);
+        Ok(())
+    }
+}
+
+// 通过 Telegram Bot API 发送消息
+pub struct TelegramNotifier {
+    http: reqwest::Client,
+    token: String,
+    chat_id: String,
+}
+
+impl TelegramNotifier {
+    pub fn new(token: String, chat_id: String) -> Self {
+        TelegramNotifier {
+            http: reqwest::Client::new(),
+            token,
+            chat_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.token);
+        send_without_url(
+            self.http
+                .post(&url)
+                .json(&json!({ "chat_id": self.chat_id, "text": alert.to_string() })),
+        )
+        .await
+    }
+
+    // getChat 同时验证 bot token 和 chat id
+    async fn check(&self) -> Result<()> {
+        let url = format!("https://api.telegram.org/bot{}/getChat", self.token);
+        send_without_url(self.http.get(&url).query(&[("chat_id", &self.chat_id)])).await
+    }
+}
+
+// 通过 Discord webhook 发送消息
+pub struct DiscordNotifier {
+    http: reqwest::Client,
+    webhook_url: String,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: String) -> Self {
+        DiscordNotifier {
+            http: reqwest::Client::new(),
+            webhook_url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        send_without_url(
+            self.http
+                .post(&self.webhook_url)
+                .json(&json!({ "content": alert.to_string() })),
+        )
+        .await
+    }
+
+    // GET webhook 地址返回 webhook 信息，不会发送消息
+    async fn check(&self) -> Result<()> {
+        send_without_url(self.http.get(&self.webhook_url)).await
+    }
+}
+
+// SMTP 服务器配置
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+// 通过 SMTP 发送邮件，同一轮的警报合并为一封
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    pub fn new(config: SmtpConfig) -> Result<Self> {
+        let mut transport =
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?.port(config.port);
+        match (config.username, config.password) {
+            (Some(username), Some(password)) => {
+                transport = transport.credentials(Credentials::new(username, password));
+            }
+            (None, None) => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Email notifier needs both SMTP username and password, or neither"
+                ))
+            }
+        }
+        let to = config
+            .to
+            .iter()
+            .map(|address| address.parse())
+            .collect::<std::result::Result<Vec<Mailbox>, _>>()?;
+        if to.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Email notifier needs at least one recipient"
+            ));
+        }
+
+        Ok(EmailNotifier {
+            transport: transport.build(),
+            from: config.from.parse()?,
+            to,
+        })
+    }
+
+    // 一轮的所有警报合并成一封邮件，每条警报一行
+    fn build_message(&self, alerts: &[Alert]) -> Result<Message> {
+        let subject = match alerts.len() {
+            1 => "Raydium Monitor: 1 alert".to_string(),
+            n => format!("Raydium Monitor: {} alerts", n),
+        };
+        let body: Vec<String> = alerts.iter().map(|alert| alert.to_string()).collect();
+
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        Ok(builder.body(body.join("\n"))?)
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        self.notify_batch(std::slice::from_ref(alert)).await
+    }
+
+    async fn notify_batch(&self, alerts: &[Alert]) -> Result<()> {
+        self.transport.send(self.build_message(alerts)?).await?;
+        Ok(())
+    }
+
+    async fn check(&self) -> Result<()> {
+        if !self.transport.test_connection().await? {
+            return Err(anyhow::anyhow!("SMTP server did not accept the connection"));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_webhook_errors_do_not_leak_url() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    // 没有监听的本地端口，连接会失败
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let notifier = DiscordNotifier::new(format!(
+        "http://127.0.0.1:{}/api/webhooks/123/secret-token",
+        port
+    ));
+
+    rt.block_on(async {
+        let alert = Alert {
+            pool_id: "POOL".to_string(),
+            symbol_a: "WSOL".to_string(),
+            symbol_b: "TEST".to_string(),
+            metric: AlertKind::Price,
+            value: 2.0,
+            threshold: 1.0,
+            summary: None,
+        };
+        for result in [notifier.check().await, notifier.notify(&alert).await] {
+            let message = format!("{:#}", result.unwrap_err());
+            assert!(!message.contains("secret-token"), "{}", message);
+        }
+    });
+}
+
+#[test]
+fn test_email_batches_alerts_into_one_message() {
+    let config = SmtpConfig {
+        host: "smtp.example.com".to_string(),
+        port: 587,
+        username: None,
+        password: None,
+        from: "monitor@example.com".to_string(),
+        to: vec!["ops@example.com".to_string()],
+    };
+    let notifier = EmailNotifier::new(config.clone()).unwrap();
+    let alert = |pool_id: &str| Alert {
+        pool_id: pool_id.to_string(),
+        symbol_a: "WSOL".to_string(),
+        symbol_b: "TEST".to_string(),
+        metric: AlertKind::Price,
+        value: 2.0,
+        threshold: 1.0,
+        summary: None,
+    };
+
+    let message = notifier
+        .build_message(&[alert("POOL1"), alert("POOL2")])
+        .unwrap();
+    let formatted = String::from_utf8(message.formatted()).unwrap();
+    assert!(formatted.contains("Subject: Raydium Monitor: 2 alerts"));
+    assert!(formatted.contains("To: ops@example.com"));
+    assert!(formatted.contains(&format!("{}\r\n{}", alert("POOL1"), alert("POOL2"))));
+
+    let message = notifier.build_message(&[alert("POOL1")]).unwrap();
+    let formatted = String::from_utf8(message.formatted()).unwrap();
+    assert!(formatted.contains("Subject: Raydium Monitor: 1 alert\r\n"));
+
+    // 只设置了用户名或密码之一
+    let result = EmailNotifier::new(SmtpConfig {
+        username: Some("user".to_string()),
+        ..config
+    });
+    assert!(result.is_err());
+}
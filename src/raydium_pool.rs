@@ -1,12 +1,31 @@
-use anyhow::Result;
+use crate::client::{check_api_response, raydium_client};
+use crate::error::{self, MonitorError};
+use crate::notifier::Alert;
+use crate::timestamp::{self, DefaultTimestamp, Timestamp};
+use crate::utils::{
+    calculate_market_cap_with_supply_cache, fdv_estimate, QuoteAsset, SharedSolPriceCache,
+    SharedSupplyCache, SolPriceCache, SupplyCache, TokenQuote, DEFAULT_SUPPLY_CACHE_TTL,
+};
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use owo_colors::{OwoColorize, Style};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tracing::Instrument;
 
 // 扩展池信息结构体，添加市值字段
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PoolInfo {
     pub id: String,
     pub symbol_a: String,
@@ -14,270 +33,4461 @@ pub struct PoolInfo {
     pub symbol_b: String,
     pub symbol_b_address: String,
     pub symbol_b_decimals: u64,
+    #[serde(default)]
+    pub symbol_a_decimals: u64,
+    // 池子类型（API 的 type 字段，Standard 为 AMM，Concentrated 为 CLMM）
+    #[serde(default)]
+    pub pool_type: String,
+    // API 的 pooltype 标签，例如 OpenBookMarket
+    #[serde(default)]
+    pub pool_tags: Vec<String>,
+    #[serde(default)]
+    pub program_id: String,
     pub volume_24h: f64,
+    pub volume_7d: Option<f64>,
+    pub volume_30d: Option<f64>,
     pub tvl: f64,
+    // 池内代币 A/B 的数量（mintAmountA/mintAmountB）
+    #[serde(default)]
+    pub reserve_a: f64,
+    #[serde(default)]
+    pub reserve_b: f64,
+    // LP 代币销毁比例(%)，越高越难撤池
+    #[serde(default)]
+    pub burn_percent: f64,
+    #[serde(default)]
+    pub lp_mint: String,
+    // 开池时间，API 返回 "0" 或缺失时为 None
+    #[serde(default)]
+    pub open_time: Option<DateTime<Utc>>,
+    // API 的 price，即 1 个代币 A 值多少个代币 B
     pub price: f64,
+    // 两个方向的价格，互为倒数；price 为 0 时都为 0
+    #[serde(default)]
+    pub price_a_in_b: f64,
+    #[serde(default)]
+    pub price_b_in_a: f64,
+    pub price_min_24h: f64,
+    pub price_max_24h: f64,
+    pub apr_24h: f64,
+    pub fee_apr_24h: f64,
+    // 交易手续费率，例如 0.0025
+    #[serde(default)]
+    pub fee_rate: f64,
+    // 24小时手续费收入（美元）
+    #[serde(default)]
+    pub volume_fee_24h: f64,
+    pub market_cap: Option<f64>,
+    // 市值使用的供应量是 RPC 限流期间沿用的旧值时，该值距今的时长
+    #[serde(skip)]
+    pub market_cap_supply_age: Option<Duration>,
+    // 市值查询得到的代币总供应量（已按精度换算）
+    #[serde(default)]
+    pub token_supply: Option<f64>,
+    // 基于池子储备的估值，不需要 RPC 查询
+    #[serde(default)]
+    pub fdv_estimate: Option<f64>,
     pub timestamp: DateTime<Utc>,
 }
 
+// 稳定/主流交易对中与 WSOL 配对时会被过滤的代币
+const STABLE_PAIR_SYMBOLS: [&str; 3] = ["USDC", "USDT", "mSOL"];
+
+impl PoolInfo {
+    // 标明方向的价格，例如 "1 WSOL = 6,948.93 $slop | 1 $slop = 0.0001439 WSOL"
+    pub fn price_label(&self) -> String {
+        if self.price_a_in_b <= 0.0 {
+            return "n/a".to_string();
+        }
+        format!(
+            "1 {} = {} {} | 1 {} = {} {}",
+            self.symbol_a,
+            format_price(self.price_a_in_b),
+            self.symbol_b,
+            self.symbol_b,
+            format_price(self.price_b_in_a),
+            self.symbol_a
+        )
+    }
+
+    // 按恒定乘积 x*y=k 估算买入 usd_size 美元代币时的价格冲击(%)：
+    // 投入 Δx 时成交均价相对现价的偏离为 Δx/(x+Δx)，x 取被买入一侧的储备价值。
+    // 两侧价值用 price × 储备折算后按比例分摊 TVL，不假设各占一半（CLMM 的两侧通常不平衡）；
+    // 被买入的是报价资产(SOL/USDC 等)之外的代币，无法判断时按买入代币 A 计算。
+    // CLMM 等非恒定乘积池子的流动性集中在当前价格附近，实际冲击通常更小，只能作为参考
+    pub fn price_impact(&self, usd_size: f64) -> f64 {
+        if usd_size <= 0.0 {
+            return 0.0;
+        }
+        if self.tvl <= 0.0
+            || self.reserve_a <= 0.0
+            || self.reserve_b <= 0.0
+            || !self.price.is_finite()
+            || self.price <= 0.0
+        {
+            return 100.0;
+        }
+        // 以代币 B 计价的两侧价值
+        let value_a = self.reserve_a * self.price;
+        let value_b = self.reserve_b;
+        let bought_value = match (
+            QuoteAsset::from_mint(&self.symbol_a_address),
+            QuoteAsset::from_mint(&self.symbol_b_address),
+        ) {
+            (Some(_), None) => value_b,
+            _ => value_a,
+        };
+        let side_value = self.tvl * bought_value / (value_a + value_b);
+        usd_size / (side_value + usd_size) * 100.0
+    }
+
+    // 代币总供应量中有多少比例(%)在本池子里，复用市值查询得到的供应量。
+    // 比例很高说明池子几乎持有全部流通量，很低则说明相对供应量而言流动性很薄；
+    // 还没有查询过供应量或无法判断哪一侧是代币时为 None
+    pub fn supply_in_pool_pct(&self) -> Option<f64> {
+        let supply = self.token_supply.filter(|supply| *supply > 0.0)?;
+        let reserve = match (
+            QuoteAsset::from_mint(&self.symbol_a_address),
+            QuoteAsset::from_mint(&self.symbol_b_address),
+        ) {
+            (Some(_), None) => self.reserve_b,
+            (None, Some(_)) => self.reserve_a,
+            _ => return None,
+        };
+        Some(reserve / supply * 100.0)
+    }
+
+    // 是否为恒定乘积的标准 AMM 池子，其他类型的价格冲击只是估算
+    pub fn is_constant_product(&self) -> bool {
+        self.pool_type == "Standard"
+    }
+
+    // 由24小时手续费和 TVL 推算的手续费年化(%)
+    pub fn estimated_fee_apr(&self) -> Option<f64> {
+        (self.tvl > 0.0).then(|| self.volume_fee_24h * 365.0 / self.tvl * 100.0)
+    }
+
+    // 推算的手续费年化与 API 返回值相差超过一倍时，API 数据可能已过期
+    pub fn fee_apr_diverges(&self) -> bool {
+        match self.estimated_fee_apr() {
+            Some(estimated) if estimated > 0.0 && self.fee_apr_24h > 0.0 => {
+                let ratio = estimated / self.fee_apr_24h;
+                !(0.5..=2.0).contains(&ratio)
+            }
+            _ => false,
+        }
+    }
+
+    // 输出中显示的简短类型标签
+    pub fn type_tag(&self) -> &str {
+        match self.pool_type.as_str() {
+            "Standard" => "AMM",
+            "Concentrated" => "CLMM",
+            "" => "?",
+            other => other,
+        }
+    }
+
+    // 按类型过滤：匹配 type、简短标签（amm/clmm）或 pooltype 标签，不区分大小写
+    pub fn matches_pool_type(&self, pool_type: &str) -> bool {
+        self.pool_type.eq_ignore_ascii_case(pool_type)
+            || self.type_tag().eq_ignore_ascii_case(pool_type)
+            || self
+                .pool_tags
+                .iter()
+                .any(|tag| tag.eq_ignore_ascii_case(pool_type))
+    }
+
+    // 池子年龄（以快照时间计算），开池时间未知时为 None
+    pub fn age(&self) -> Option<chrono::Duration> {
+        self.open_time.map(|open_time| self.timestamp - open_time)
+    }
+
+    // 交易对名称，例如 WSOL/USDC
+    pub fn pair_name(&self) -> String {
+        format!("{}/{}", self.symbol_a, self.symbol_b)
+    }
+
+    // WSOL 与 USDC/USDT/mSOL 组成的交易对，监控时会被过滤
+    pub fn is_stable_pair(&self) -> bool {
+        (self.symbol_a == "WSOL" && STABLE_PAIR_SYMBOLS.contains(&self.symbol_b.as_str()))
+            || (self.symbol_b == "WSOL" && STABLE_PAIR_SYMBOLS.contains(&self.symbol_a.as_str()))
+    }
+
+    // 当前价格在24小时区间中的位置(%)，区间为空时返回 None
+    pub fn price_range_position(&self) -> Option<f64> {
+        let range = self.price_max_24h - self.price_min_24h;
+        if range <= 0.0 {
+            return None;
+        }
+        Some((self.price - self.price_min_24h) / range * 100.0)
+    }
+}
+
 // 扩展历史数据结构体，添加市值
-#[derive(Debug, Clone)]
-pub struct HistoricalData {
+// 时间戳类型默认是 DateTime<Utc>，见 timestamp 模块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalData<T = DefaultTimestamp> {
     pub volume_24h: f64,
+    // 7天交易量，用于在缺少24小时历史时估算变化
+    #[serde(default)]
+    pub volume_7d: Option<f64>,
     pub price: f64,
     pub tvl: f64,
-    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub apr: f64,
+    // 旧的历史文件和 CSV 导入没有储备数据，为 0
+    #[serde(default)]
+    pub reserve_a: f64,
+    #[serde(default)]
+    pub reserve_b: f64,
+    pub timestamp: T,
+}
+
+// 历史记录中可比较的指标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryMetric {
+    Price,
+    Volume24h,
+    Tvl,
+    Apr,
+    ReserveA,
+    ReserveB,
+}
+
+impl HistoryMetric {
+    fn value<T>(self, record: &HistoricalData<T>) -> f64 {
+        match self {
+            HistoryMetric::Price => record.price,
+            HistoryMetric::Volume24h => record.volume_24h,
+            HistoryMetric::Tvl => record.tvl,
+            HistoryMetric::Apr => record.apr,
+            HistoryMetric::ReserveA => record.reserve_a,
+            HistoryMetric::ReserveB => record.reserve_b,
+        }
+    }
+}
+
+// CSV 导入的一行历史快照
+#[derive(Debug, Deserialize)]
+struct CsvSnapshot {
+    pool_id: String,
+    timestamp: DateTime<Utc>,
+    price: f64,
+    volume_24h: f64,
+    tvl: f64,
 }
 
 // 扩展变化指标结构体，添加市值变化
-#[derive(Debug)]
+// 没有足够早的历史记录时对应字段为 None
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChangeMetrics {
-    pub volume_change_5m: f64,  // 5分钟变化
-    pub volume_change_15m: f64, // 15分钟变化
-    pub volume_change_1h: f64,  // 1小时变化
-    pub volume_change_24h: f64, // 24小时变化
-    pub price_change_5m: f64,   // 5分钟变化
-    pub price_change_15m: f64,  // 15分钟变化
-    pub price_change_1h: f64,   // 1小时变化
-    pub price_change_24h: f64,  // 24小时变化
-    pub tvl_change_24h: f64,
+    pub volume_change_5m: Option<f64>,  // 5分钟变化
+    pub volume_change_15m: Option<f64>, // 15分钟变化
+    pub volume_change_1h: Option<f64>,  // 1小时变化
+    pub volume_change_24h: Option<f64>, // 24小时变化
+    pub price_change_5m: Option<f64>,   // 5分钟变化
+    pub price_change_15m: Option<f64>,  // 15分钟变化
+    pub price_change_1h: Option<f64>,   // 1小时变化
+    pub price_change_24h: Option<f64>,  // 24小时变化
+    pub tvl_change_5m: Option<f64>,     // 5分钟变化
+    pub tvl_change_15m: Option<f64>,    // 15分钟变化
+    pub tvl_change_1h: Option<f64>,     // 1小时变化
+    pub tvl_change_24h: Option<f64>,
+    pub apr_change_24h: Option<f64>,
+    pub reserve_a_change_5m: Option<f64>,
+    pub reserve_b_change_5m: Option<f64>,
+    // 各窗口参考数据的实际年龄（较早一侧的记录距最新记录的时长），采样不均匀时可能远大于窗口
+    #[serde(default)]
+    pub reference_age_5m: Option<Duration>,
+    #[serde(default)]
+    pub reference_age_15m: Option<Duration>,
+    #[serde(default)]
+    pub reference_age_1h: Option<Duration>,
+    #[serde(default)]
+    pub reference_age_24h: Option<Duration>,
+}
+
+// 参考数据年龄超过窗口的该倍数时，提示变化率基于较旧的数据
+const STALE_REFERENCE_FACTOR: f64 = 1.5;
+
+impl ChangeMetrics {
+    // 参考数据明显早于回看时间点的窗口及其实际年龄
+    pub fn stale_windows(&self) -> Vec<(&'static str, Duration)> {
+        let minute = Duration::from_secs(60);
+        [
+            ("5m", 5 * minute, self.reference_age_5m),
+            ("15m", 15 * minute, self.reference_age_15m),
+            ("1h", 60 * minute, self.reference_age_1h),
+            ("24h", 24 * 60 * minute, self.reference_age_24h),
+        ]
+        .into_iter()
+        .filter_map(|(label, window, age)| {
+            age.filter(|age| *age > window.mul_f64(STALE_REFERENCE_FACTOR))
+                .map(|age| (label, age))
+        })
+        .collect()
+    }
+
+    // 5分钟/15分钟/1小时中 TVL 下降最多的窗口，没有任何数据时返回 None
+    pub fn worst_short_term_tvl_change(&self) -> Option<(&'static str, f64)> {
+        [
+            ("5m", self.tvl_change_5m),
+            ("15m", self.tvl_change_15m),
+            ("1h", self.tvl_change_1h),
+        ]
+        .into_iter()
+        .filter_map(|(window, change)| change.map(|change| (window, change)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
 }
 
+// 单个池子的历史记录，按时间顺序排列
+pub type PoolHistory = VecDeque<HistoricalData>;
+
+// 内存中历史数据的规模，用于长期运行时调整保留期和容量
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryStats {
+    pub pools: usize,
+    pub total_records: usize,
+    pub min_records_per_pool: usize,
+    pub max_records_per_pool: usize,
+    pub avg_records_per_pool: f64,
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+    // 按记录结构体大小估算的占用字节数，不含 HashMap 和 VecDeque 的额外开销
+    pub estimated_bytes: usize,
+}
+
+const DEFAULT_RETENTION_DAYS: i64 = 7;
+const DEFAULT_SOL_CACHE_TTL: Duration = Duration::from_secs(60);
+
 pub struct PoolMonitor {
-    pub historical_data: Arc<Mutex<HashMap<String, Vec<HistoricalData>>>>,
+    pub historical_data: Arc<Mutex<HashMap<String, PoolHistory>>>,
+    // 每个池子最多保留的记录数，None 表示只按时间清理
+    pub capacity: Option<usize>,
+    // 历史记录的保留期，默认7天
+    pub retention: chrono::Duration,
+    // 历史数据文件，None 表示不持久化
+    pub db_path: Option<PathBuf>,
+    // 计算市值时共享的 SOL 价格缓存
+    pub sol_price_cache: SharedSolPriceCache,
     pub last_update: Arc<Mutex<DateTime<Utc>>>,
+    // 上一次快照中的池子ID，None 表示还没有快照
+    pub previous_ids: Arc<Mutex<Option<HashSet<String>>>>,
+    // 每个池子每种警报上一次触发的时间，用于冷却
+    pub last_alert_times: Arc<Mutex<HashMap<AlertKey, DateTime<Utc>>>>,
+    pub price_targets: Arc<Mutex<Vec<PriceTarget>>>,
+    // 每个设置了目标价的池子上一次观察到的价格，用于判断穿越
+    pub last_target_prices: Arc<Mutex<HashMap<String, f64>>>,
+    // 按池子覆盖的警报阈值
+    pub pool_thresholds: Arc<Mutex<HashMap<String, PoolThreshold>>>,
+    pub momentum_weights: MomentumWeights,
+    // 最近一次轮询显示的池子，由价格轮询写入，市值轮询读取
+    pub shown_pools: Arc<Mutex<Vec<PoolInfo>>>,
+    // 市值轮询的结果，价格轮询每次显示前读取
+    pub market_caps: Arc<Mutex<HashMap<String, CachedMarketCap>>>,
+    // 最近一次成功查询的代币供应量，RPC 限流时使用
+    pub supply_cache: SharedSupplyCache,
 }
 
-impl PoolMonitor {
-    pub fn new() -> Self {
-        PoolMonitor {
-            historical_data: Arc::new(Mutex::new(HashMap::new())),
-            last_update: Arc::new(Mutex::new(Utc::now())),
+// 市值轮询得到的市值；沿用旧供应量计算时记录该供应量的查询时间
+#[derive(Debug, Clone, Copy)]
+pub struct CachedMarketCap {
+    pub market_cap: f64,
+    pub token_supply: Option<f64>,
+    pub supply_fetched_at: Option<std::time::Instant>,
+}
+
+// 动量分数各项输入的权重
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MomentumWeights {
+    pub price_5m: f64,
+    pub price_1h: f64,
+    pub volume_1h: f64,
+}
+
+impl Default for MomentumWeights {
+    fn default() -> Self {
+        MomentumWeights {
+            price_5m: 0.4,
+            price_1h: 0.3,
+            volume_1h: 0.3,
         }
     }
+}
 
-    // 计算变化率
-    pub fn calculate_change(old_value: f64, new_value: f64) -> f64 {
-        ((new_value - old_value) / old_value) * 100.0
+// 格式：<price_5m>,<price_1h>,<volume_1h>，例如 0.4,0.3,0.3
+impl FromStr for MomentumWeights {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let weights = s
+            .split(',')
+            .map(|w| w.trim().parse::<f64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .with_context(|| format!("Invalid momentum weights '{}'", s))?;
+        match weights[..] {
+            [price_5m, price_1h, volume_1h]
+                if weights.iter().all(|w| *w >= 0.0) && weights.iter().sum::<f64>() > 0.0 =>
+            {
+                Ok(MomentumWeights {
+                    price_5m,
+                    price_1h,
+                    volume_1h,
+                })
+            }
+            _ => Err(anyhow::anyhow!(
+                "Invalid momentum weights '{}', expected three non-negative numbers <price_5m>,<price_1h>,<volume_1h>",
+                s
+            )),
+        }
     }
+}
 
-    // 修改获取变化指标的方法
-    pub async fn get_changes(&self, pool_id: &str, _minutes: i64) -> Option<ChangeMetrics> {
-        let historical_data = self.historical_data.lock().await;
-        let pool_history = historical_data.get(pool_id)?;
+// 动量分数中各输入的归一化尺度(%)：变化率先除以尺度再取 tanh，
+// 映射到 (-1, 1)，单个极端值最多贡献其权重，不会主导总分
+const MOMENTUM_SCALE_PRICE_5M: f64 = 2.0;
+const MOMENTUM_SCALE_PRICE_1H: f64 = 5.0;
+const MOMENTUM_SCALE_VOLUME_1H: f64 = 50.0;
 
-        if pool_history.is_empty() {
-            return None;
-        }
+// 绝对价格目标，价格穿越 above/below 时报警
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTarget {
+    pub pool_id: String,
+    pub above: Option<f64>,
+    pub below: Option<f64>,
+}
 
-        let latest = pool_history.last()?;
+// 解析 <pool_id>:<above|below>:<value>
+impl FromStr for PriceTarget {
+    type Err = anyhow::Error;
 
-        // 获取不同时间点的历史数据
-        let time_5m = latest.timestamp - chrono::Duration::minutes(5);
-        let time_15m = latest.timestamp - chrono::Duration::minutes(15);
-        let time_1h = latest.timestamp - chrono::Duration::hours(1);
-        let time_24h = latest.timestamp - chrono::Duration::hours(24);
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [pool_id, direction, value] = parts.as_slice() else {
+            return Err(anyhow::anyhow!(
+                "Invalid price target '{}', expected <pool_id>:<above|below>:<value>",
+                s
+            ));
+        };
+        let value: f64 = value
+            .parse()
+            .with_context(|| format!("Invalid price target value '{}'", value))?;
 
-        // 查找最接近的历史记录
-        let record_5m = pool_history.iter().rev().find(|r| r.timestamp <= time_5m);
-        let record_15m = pool_history.iter().rev().find(|r| r.timestamp <= time_15m);
-        let record_1h = pool_history.iter().rev().find(|r| r.timestamp <= time_1h);
-        let record_24h = pool_history.iter().rev().find(|r| r.timestamp <= time_24h);
+        let (above, below) = match direction.to_lowercase().as_str() {
+            "above" => (Some(value), None),
+            "below" => (None, Some(value)),
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Unknown price target direction '{}', expected above or below",
+                    other
+                ))
+            }
+        };
 
-        Some(ChangeMetrics {
-            volume_change_5m: record_5m
-                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h))
-                .unwrap_or(0.0),
-            volume_change_15m: record_15m
-                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h))
-                .unwrap_or(0.0),
-            volume_change_1h: record_1h
-                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h))
-                .unwrap_or(0.0),
-            volume_change_24h: record_24h
-                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h))
-                .unwrap_or(0.0),
-            price_change_5m: record_5m
-                .map(|r| Self::calculate_change(r.price, latest.price))
-                .unwrap_or(0.0),
-            price_change_15m: record_15m
-                .map(|r| Self::calculate_change(r.price, latest.price))
-                .unwrap_or(0.0),
-            price_change_1h: record_1h
-                .map(|r| Self::calculate_change(r.price, latest.price))
-                .unwrap_or(0.0),
-            price_change_24h: record_24h
-                .map(|r| Self::calculate_change(r.price, latest.price))
-                .unwrap_or(0.0),
-            tvl_change_24h: record_24h
-                .map(|r| Self::calculate_change(r.tvl, latest.tvl))
-                .unwrap_or(0.0),
+        Ok(PriceTarget {
+            pool_id: pool_id.to_string(),
+            above,
+            below,
         })
     }
+}
 
-    // 修正后的更新历史数据方法
-    pub async fn update_historical_data(&self, pool_info: &PoolInfo) {
-        let mut historical_data = self.historical_data.lock().await;
-        let pool_history = historical_data
-            .entry(pool_info.id.clone())
-            .or_insert_with(Vec::new);
+// 单个池子的警报阈值，覆盖全局的 price_alert/volume_alert，None 表示沿用全局值
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolThreshold {
+    pub pool_id: String,
+    pub price_alert: Option<f64>,
+    pub volume_alert: Option<f64>,
+}
 
-        // 添加新的历史记录，包含市值数据
-        pool_history.push(HistoricalData {
-            volume_24h: pool_info.volume_24h,
-            price: pool_info.price,
-            tvl: pool_info.tvl,
-            timestamp: pool_info.timestamp,
-        });
+// 解析 <pool_id>:<price>:<volume>，留空表示沿用全局值，例如 <pool_id>::10
+impl FromStr for PoolThreshold {
+    type Err = anyhow::Error;
 
-        // 保留最近7天的数据
-        let week_ago = Utc::now() - chrono::Duration::days(7);
-        pool_history.retain(|record| record.timestamp > week_ago);
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [pool_id, price_alert, volume_alert] = parts.as_slice() else {
+            return Err(anyhow::anyhow!(
+                "Invalid pool threshold '{}', expected <pool_id>:<price>:<volume>",
+                s
+            ));
+        };
+        let parse = |value: &str| -> Result<Option<f64>> {
+            if value.is_empty() {
+                return Ok(None);
+            }
+            let value: f64 = value
+                .parse()
+                .with_context(|| format!("Invalid pool threshold value '{}'", value))?;
+            if value < 0.0 {
+                return Err(anyhow::anyhow!(
+                    "alert thresholds must not be negative, got {}",
+                    value
+                ));
+            }
+            Ok(Some(value))
+        };
 
-        // 可选：输出调试信息
-        log::debug!(
-            "Updated historical data for pool {}: {} records stored",
-            pool_info.id,
-            pool_history.len()
-        );
+        Ok(PoolThreshold {
+            pool_id: pool_id.to_string(),
+            price_alert: parse(price_alert)?,
+            volume_alert: parse(volume_alert)?,
+        })
     }
 }
 
-pub async fn fetch_raydium_data(page: u32) -> Result<Value> {
-    let url = format!(
-        "https://api-v3.raydium.io/pools/info/list?poolType=all&poolSortField=volume24h&sortType=desc&pageSize=100&page={}",
-        page
-    );
-    let response = reqwest::get(&url).await?;
-    let json: Value = response.json().await?;
-    Ok(json)
+// 价格穿越目标价的事件
+#[derive(Debug, Clone)]
+pub struct PriceCrossing {
+    pub pool_id: String,
+    pub symbol_a: String,
+    pub symbol_b: String,
+    pub level: f64,
+    pub price: f64,
+    pub upward: bool,
 }
 
-// 首先创建一个用于返回的数据结构
-#[derive(Debug, Clone)]
-pub struct PoolDataResult {
-    pub pools: Vec<PoolInfo>,
-    pub timestamp: DateTime<Utc>,
+// 两次轮询之间单个指标的变化
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MetricDelta {
+    pub absolute: f64,
+    // 旧值为 0 时无法计算百分比
+    pub percent: Option<f64>,
 }
 
-pub async fn check_raydium_pools() -> Result<PoolDataResult> {
-    let data = fetch_raydium_data(1).await?;
-    let current_time = Utc::now();
-    log::info!("Checking Raydium pools at {}", current_time);
+impl MetricDelta {
+    fn between(old_value: f64, new_value: f64) -> Self {
+        MetricDelta {
+            absolute: new_value - old_value,
+            percent: (old_value != 0.0)
+                .then(|| PoolMonitor::calculate_change(old_value, new_value)),
+        }
+    }
+}
 
-    if let Some(pools) = data["data"]["data"].as_array() {
-        let mut pool_infos: Vec<PoolInfo> = Vec::new();
+// 两次快照之间单个池子的差异
+#[derive(Debug, Clone, Serialize)]
+pub enum PoolDelta {
+    // 两次快照中都存在
+    Changed {
+        pool_id: String,
+        price: MetricDelta,
+        volume_24h: MetricDelta,
+        tvl: MetricDelta,
+    },
+    // 只存在于当前快照
+    New {
+        pool_id: String,
+    },
+    // 只存在于上一次快照
+    Gone {
+        pool_id: String,
+    },
+}
 
-        for pool in pools {
-            if let (
-                Some(id),
-                Some(symbol_a),
-                Some(symbol_b),
-                Some(symbol_a_address),
-                Some(symbol_b_address),
-                Some(symbol_b_decimals),
-            ) = (
-                pool["id"].as_str(),
-                pool["mintA"]["symbol"].as_str(),
-                pool["mintB"]["symbol"].as_str(),
-                pool["mintA"]["address"].as_str(),
-                pool["mintB"]["address"].as_str(),
-                pool["mintB"]["decimals"].as_u64(),
-            ) {
-                // 过滤特定池
-                if (symbol_a == "WSOL"
-                    && (symbol_b == "USDC" || symbol_b == "USDT" || symbol_b == "mSOL"))
-                    || (symbol_b == "WSOL"
-                        && (symbol_a == "USDC" || symbol_a == "USDT" || symbol_a == "mSOL"))
-                {
-                    continue;
-                }
+// ids 接口每次查询的最大池子数量
+const MAX_IDS_PER_REQUEST: usize = 50;
 
-                let volume_24h = pool["day"]["volume"].as_f64().unwrap_or(0.0);
-                let tvl = pool["tvl"].as_f64().unwrap_or(0.0);
-                let price = pool["price"].as_f64().unwrap_or(0.0);
+// 分页并发请求的最大数量，避免触发 API 限流
+const MAX_PAGE_CONCURRENCY: usize = 3;
 
-                pool_infos.push(PoolInfo {
-                    id: id.to_string(),
-                    symbol_a: symbol_a.to_string(),
-                    symbol_a_address: symbol_a_address.to_string(),
-                    symbol_b: symbol_b.to_string(),
-                    symbol_b_address: symbol_b_address.to_string(),
-                    symbol_b_decimals,
-                    volume_24h,
-                    tvl,
-                    price,
-                    timestamp: current_time,
-                });
-            }
+// 单个池子历史记录超过该数量时触发降采样
+const HISTORY_COMPACT_THRESHOLD: usize = 2000;
+
+// 数据未变化时，两条历史记录之间的最大间隔
+fn history_dedup_max_gap() -> chrono::Duration {
+    chrono::Duration::minutes(10)
+}
+
+// 计算交易量 z-score 所需的最少历史样本数
+const MIN_ZSCORE_SAMPLES: usize = 10;
+
+// 计算价格相关系数所需的最少对齐样本数
+const MIN_CORRELATION_SAMPLES: usize = 10;
+
+// 警报类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    Price,
+    Volume,
+    TvlDrain,
+    VolumeSpike,
+    ReserveShift,
+    // 两个池子的价格相关系数跌破阈值（--compare）
+    Decoupling,
+    // 快照中新出现的池子，value 为上线时的价格
+    NewPool,
+    // 完整快照中消失的池子
+    PoolDelisted,
+    // 价格穿越目标价，value 为当前价格，threshold 为目标价
+    PriceTarget,
+}
+
+// (池子ID, 警报类型)
+pub type AlertKey = (String, AlertKind);
+
+impl Default for PoolMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// PoolMonitor 的配置项，build() 时如果历史数据文件已存在会先加载
+pub struct PoolMonitorBuilder {
+    retention: chrono::Duration,
+    capacity: Option<usize>,
+    db_path: Option<PathBuf>,
+    sol_cache_ttl: Duration,
+    sol_price_max_deviation: f64,
+    supply_cache_ttl: Duration,
+    momentum_weights: MomentumWeights,
+}
+
+impl Default for PoolMonitorBuilder {
+    fn default() -> Self {
+        PoolMonitorBuilder {
+            retention: chrono::Duration::days(DEFAULT_RETENTION_DAYS),
+            capacity: None,
+            db_path: None,
+            sol_cache_ttl: DEFAULT_SOL_CACHE_TTL,
+            sol_price_max_deviation: 0.0,
+            supply_cache_ttl: DEFAULT_SUPPLY_CACHE_TTL,
+            momentum_weights: MomentumWeights::default(),
         }
+    }
+}
 
-        // 按24小时交易量排序
-        pool_infos.sort_by(|a, b| b.volume_24h.partial_cmp(&a.volume_24h).unwrap());
+impl PoolMonitorBuilder {
+    pub fn retention(mut self, retention: chrono::Duration) -> Self {
+        self.retention = retention;
+        self
+    }
 
-        Ok(PoolDataResult {
-            pools: pool_infos,
-            timestamp: current_time,
-        })
-    } else {
-        Err(anyhow::anyhow!("Failed to parse pool data"))
+    // 0 表示不限制条数
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = (capacity > 0).then_some(capacity);
+        self
+    }
+
+    pub fn db_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.db_path = Some(path.into());
+        self
+    }
+
+    pub fn sol_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.sol_cache_ttl = ttl;
+        self
+    }
+
+    pub fn sol_price_max_deviation(mut self, max_deviation: f64) -> Self {
+        self.sol_price_max_deviation = max_deviation;
+        self
+    }
+
+    // 0 表示不缓存代币供应量
+    pub fn supply_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.supply_cache_ttl = ttl;
+        self
+    }
+
+    pub fn momentum_weights(mut self, weights: MomentumWeights) -> Self {
+        self.momentum_weights = weights;
+        self
+    }
+
+    pub async fn build(self) -> Result<PoolMonitor> {
+        let pool_monitor = PoolMonitor {
+            capacity: self.capacity,
+            retention: self.retention,
+            db_path: self.db_path,
+            sol_price_cache: SolPriceCache::new(self.sol_cache_ttl)
+                .with_max_deviation(self.sol_price_max_deviation)
+                .into_shared(),
+            supply_cache: SupplyCache::shared(self.supply_cache_ttl),
+            momentum_weights: self.momentum_weights,
+            ..PoolMonitor::new()
+        };
+        if let Some(path) = pool_monitor.db_path.as_deref().filter(|path| path.exists()) {
+            pool_monitor.load_history(path).await?;
+            tracing::info!("Loaded historical data from {}", path.display());
+        }
+        Ok(pool_monitor)
     }
 }
 
-// 添加一个格式化函数用于显示
-pub async fn format_pool_data(
-    pool_data: &PoolDataResult,
-    pool_monitor: &PoolMonitor,
-    top_n: usize,
-    price_alert: f64,
-    volume_alert: f64,
-) -> String {
-    let mut result = String::new();
-    result.push_str(&format!(
-        "🕒 Update time: {}\n\n",
-        pool_data.timestamp.format("%Y-%m-%d %H:%M:%S")
-    ));
+impl PoolMonitor {
+    pub fn new() -> Self {
+        PoolMonitor {
+            historical_data: Arc::new(Mutex::new(HashMap::new())),
+            last_update: Arc::new(Mutex::new(timestamp::now())),
+            previous_ids: Arc::new(Mutex::new(None)),
+            last_alert_times: Arc::new(Mutex::new(HashMap::new())),
+            price_targets: Arc::new(Mutex::new(Vec::new())),
+            last_target_prices: Arc::new(Mutex::new(HashMap::new())),
+            pool_thresholds: Arc::new(Mutex::new(HashMap::new())),
+            capacity: None,
+            retention: chrono::Duration::days(DEFAULT_RETENTION_DAYS),
+            db_path: None,
+            sol_price_cache: SolPriceCache::shared(DEFAULT_SOL_CACHE_TTL),
+            momentum_weights: MomentumWeights::default(),
+            shown_pools: Arc::new(Mutex::new(Vec::new())),
+            market_caps: Arc::new(Mutex::new(HashMap::new())),
+            supply_cache: SupplyCache::shared(DEFAULT_SUPPLY_CACHE_TTL),
+        }
+    }
 
-    for pool_info in pool_data.pools.iter().take(top_n) {
-        if let Some(changes) = pool_monitor.get_changes(&pool_info.id, 5).await {
-            result.push_str(&format!(
-                "🔄 {} ({}/{})\n\
-                 💰 ${:.6}\n\
-                 📈 Price: 5m:{:.2}% | 15m:{:.2}% | 1h:{:.2}% | 24h:{:.2}%\n\
-                 📊 Vol: ${:.2}M\n\
-                 📊 Vol Chg: 5m:{:.2}% | 15m:{:.2}% | 1h:{:.2}% | 24h:{:.2}%\n",
-                pool_info.id,
-                pool_info.symbol_a,
-                pool_info.symbol_b,
-                pool_info.price,
-                changes.price_change_5m,
-                changes.price_change_15m,
-                changes.price_change_1h,
-                changes.price_change_24h,
-                pool_info.volume_24h / 1_000_000.0,
-                changes.volume_change_5m,
-                changes.volume_change_15m,
-                changes.volume_change_1h,
-                changes.volume_change_24h,
-            ));
+    // 每个池子最多保留 capacity 条记录，满了丢弃最旧的，与7天的时间清理同时生效
+    pub fn with_capacity(capacity: usize) -> Self {
+        PoolMonitor {
+            capacity: Some(capacity.max(1)),
+            ..Self::new()
+        }
+    }
 
-            // 警报检查
-            if changes.price_change_5m.abs() > price_alert {
-                result.push_str(&format!(
-                    "⚠️ 价格5分钟变化显著: {:.2}%\n",
-                    changes.price_change_5m
-                ));
+    pub fn with_momentum_weights(mut self, weights: MomentumWeights) -> Self {
+        self.momentum_weights = weights;
+        self
+    }
+
+    pub fn builder() -> PoolMonitorBuilder {
+        PoolMonitorBuilder::default()
+    }
+
+    // 保存到构建时指定的历史数据文件，未指定时不做任何事
+    pub async fn save(&self) -> Result<()> {
+        match &self.db_path {
+            Some(path) => self.save_history(path).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn add_price_target(&self, target: PriceTarget) {
+        self.price_targets.lock().await.push(target);
+    }
+
+    // 同一池子重复设置时以最后一次为准
+    pub async fn add_pool_threshold(&self, threshold: PoolThreshold) {
+        self.pool_thresholds
+            .lock()
+            .await
+            .insert(threshold.pool_id.clone(), threshold);
+    }
+
+    // 应用池子覆盖后的阈值
+    pub async fn thresholds_for(&self, pool_id: &str, global: &AlertThresholds) -> AlertThresholds {
+        let pool_thresholds = self.pool_thresholds.lock().await;
+        match pool_thresholds.get(pool_id) {
+            Some(threshold) => AlertThresholds {
+                price_alert: threshold.price_alert.unwrap_or(global.price_alert),
+                volume_alert: threshold.volume_alert.unwrap_or(global.volume_alert),
+                ..*global
+            },
+            None => *global,
+        }
+    }
+
+    // 检查目标价穿越（边沿触发：只在穿越的那一次报警，持续在目标价之外不会重复报警）
+    pub async fn check_price_targets(&self, current: &PoolDataResult) -> Vec<PriceCrossing> {
+        let price_targets = self.price_targets.lock().await;
+        let mut last_target_prices = self.last_target_prices.lock().await;
+        let mut crossings = Vec::new();
+
+        for pool_info in &current.pools {
+            let targets: Vec<&PriceTarget> = price_targets
+                .iter()
+                .filter(|target| target.pool_id == pool_info.id)
+                .collect();
+            if targets.is_empty() {
+                continue;
             }
-            if changes.volume_change_5m.abs() > volume_alert {
-                result.push_str(&format!(
-                    "⚠️ 交易量5分钟变化显著: {:.2}%\n",
-                    changes.volume_change_5m
-                ));
+
+            let price = pool_info.price;
+            if let Some(previous) = last_target_prices.insert(pool_info.id.clone(), price) {
+                for target in targets {
+                    let crossed_up = target
+                        .above
+                        .filter(|level| previous <= *level && price > *level)
+                        .map(|level| (level, true));
+                    let crossed_down = target
+                        .below
+                        .filter(|level| previous >= *level && price < *level)
+                        .map(|level| (level, false));
+
+                    for (level, upward) in crossed_up.into_iter().chain(crossed_down) {
+                        crossings.push(PriceCrossing {
+                            pool_id: pool_info.id.clone(),
+                            symbol_a: pool_info.symbol_a.clone(),
+                            symbol_b: pool_info.symbol_b.clone(),
+                            level,
+                            price,
+                            upward,
+                        });
+                    }
+                }
+            }
+        }
+
+        crossings
+    }
+
+    // 判断警报是否需要发出：冷却期内不重复报警，指标回落到阈值以下后重置冷却
+    pub async fn should_alert(
+        &self,
+        pool_id: &str,
+        kind: AlertKind,
+        triggered: bool,
+        now: DateTime<Utc>,
+        cooldown: chrono::Duration,
+    ) -> bool {
+        let mut last_alert_times = self.last_alert_times.lock().await;
+        let key = (pool_id.to_string(), kind);
+
+        if !triggered {
+            last_alert_times.remove(&key);
+            return false;
+        }
+
+        match last_alert_times.get(&key) {
+            Some(last) if now - *last < cooldown => false,
+            _ => {
+                last_alert_times.insert(key, now);
+                true
             }
+        }
+    }
 
-            result.push_str("----------------------\n");
+    // 与上一次快照对比，找出新出现的池子；第一次快照不报告
+    pub async fn new_pools<'a>(&self, current: &'a PoolDataResult) -> Vec<&'a PoolInfo> {
+        let previous_ids = self.previous_ids.lock().await;
+        match previous_ids.as_ref() {
+            Some(previous_ids) => current
+                .pools
+                .iter()
+                .filter(|pool_info| !previous_ids.contains(&pool_info.id))
+                .collect(),
+            None => Vec::new(),
         }
     }
 
-    result
+    // 上一次快照中存在、但当前快照中消失的池子
+    // 只有完整（全部分页）的快照才判断，避免把跌出第一页的池子误报为下架
+    pub async fn disappeared_pools(&self, current: &PoolDataResult) -> Vec<String> {
+        if !current.complete {
+            return Vec::new();
+        }
+
+        let previous_ids = self.previous_ids.lock().await;
+        let current_ids: HashSet<&str> = current.pools.iter().map(|p| p.id.as_str()).collect();
+        let mut disappeared: Vec<String> = previous_ids
+            .iter()
+            .flatten()
+            .filter(|id| !current_ids.contains(id.as_str()))
+            .cloned()
+            .collect();
+        disappeared.sort();
+        disappeared
+    }
+
+    // 记录当前快照的池子ID，供下一次对比
+    pub async fn record_snapshot(&self, current: &PoolDataResult) {
+        let mut previous_ids = self.previous_ids.lock().await;
+        *previous_ids = Some(current.pools.iter().map(|p| p.id.clone()).collect());
+    }
+
+    // 记录本轮显示的池子，供独立的市值轮询使用
+    pub async fn set_shown_pools(&self, pools: &[PoolInfo]) {
+        *self.shown_pools.lock().await = pools.to_vec();
+    }
+
+    // 为最近显示的池子重新查询市值；查询失败的池子保留上一次的值，
+    // 不再显示的池子从缓存中移除。返回成功更新的数量
+    pub async fn refresh_market_caps(
+        &self,
+        client: &RpcClient,
+        sol_price_cache: &SharedSolPriceCache,
+        concurrency: usize,
+    ) -> usize {
+        let mut pools = self.shown_pools.lock().await.clone();
+        if pools.is_empty() {
+            return 0;
+        }
+        fill_market_caps(
+            &mut pools,
+            client,
+            sol_price_cache,
+            &self.supply_cache,
+            concurrency,
+        )
+        .await;
+
+        let mut market_caps = self.market_caps.lock().await;
+        market_caps.retain(|pool_id, _| pools.iter().any(|pool_info| &pool_info.id == pool_id));
+        let mut updated = 0;
+        for pool_info in &pools {
+            if let Some(market_cap) = pool_info.market_cap {
+                let supply_fetched_at = pool_info
+                    .market_cap_supply_age
+                    .and_then(|age| std::time::Instant::now().checked_sub(age));
+                market_caps.insert(
+                    pool_info.id.clone(),
+                    CachedMarketCap {
+                        market_cap,
+                        token_supply: pool_info.token_supply,
+                        supply_fetched_at,
+                    },
+                );
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    // 显式刷新：让缓存的代币供应量过期后重新查询市值
+    pub async fn force_refresh_market_caps(
+        &self,
+        client: &RpcClient,
+        sol_price_cache: &SharedSolPriceCache,
+        concurrency: usize,
+    ) -> usize {
+        self.supply_cache.lock().await.invalidate();
+        self.refresh_market_caps(client, sol_price_cache, concurrency)
+            .await
+    }
+
+    // 把缓存的市值填入本轮数据，还没有查询过的池子保持 None
+    pub async fn apply_market_caps(&self, pools: &mut [PoolInfo]) {
+        let market_caps = self.market_caps.lock().await;
+        for pool_info in pools {
+            let cached = market_caps.get(&pool_info.id);
+            pool_info.market_cap = cached.map(|cached| cached.market_cap);
+            pool_info.token_supply = cached.and_then(|cached| cached.token_supply);
+            pool_info.market_cap_supply_age = cached
+                .and_then(|cached| cached.supply_fetched_at)
+                .map(|fetched_at| fetched_at.elapsed());
+        }
+    }
+
+    // 逐次轮询的差异，不依赖历史数据
+    pub fn snapshot_diff(previous: &PoolDataResult, current: &PoolDataResult) -> Vec<PoolDelta> {
+        let previous_pools: HashMap<&str, &PoolInfo> = previous
+            .pools
+            .iter()
+            .map(|pool_info| (pool_info.id.as_str(), pool_info))
+            .collect();
+        let current_ids: HashSet<&str> = current.pools.iter().map(|p| p.id.as_str()).collect();
+
+        let mut deltas: Vec<PoolDelta> = current
+            .pools
+            .iter()
+            .map(
+                |pool_info| match previous_pools.get(pool_info.id.as_str()) {
+                    Some(old) => PoolDelta::Changed {
+                        pool_id: pool_info.id.clone(),
+                        price: MetricDelta::between(old.price, pool_info.price),
+                        volume_24h: MetricDelta::between(old.volume_24h, pool_info.volume_24h),
+                        tvl: MetricDelta::between(old.tvl, pool_info.tvl),
+                    },
+                    None => PoolDelta::New {
+                        pool_id: pool_info.id.clone(),
+                    },
+                },
+            )
+            .collect();
+        deltas.extend(
+            previous
+                .pools
+                .iter()
+                .filter(|pool_info| !current_ids.contains(pool_info.id.as_str()))
+                .map(|pool_info| PoolDelta::Gone {
+                    pool_id: pool_info.id.clone(),
+                }),
+        );
+        deltas
+    }
+
+    // 计算变化率
+    pub fn calculate_change(old_value: f64, new_value: f64) -> f64 {
+        ((new_value - old_value) / old_value) * 100.0
+    }
+
+    // 固定 5m/15m/1h/24h 窗口的变化指标，任意区间使用 get_change_between
+    pub async fn get_changes(&self, pool_id: &str) -> Option<ChangeMetrics> {
+        let historical_data = self.historical_data.lock().await;
+        Self::changes_in(historical_data.get(pool_id)?)
+    }
+
+    // 根据一个池子的历史记录计算变化指标，不依赖具体的时间戳类型
+    pub fn changes_in<T: Timestamp>(
+        pool_history: &VecDeque<HistoricalData<T>>,
+    ) -> Option<ChangeMetrics> {
+        let latest = pool_history.back()?;
+
+        // 获取不同时间点的参考数据
+        let minute = Duration::from_secs(60);
+        let [reference_5m, reference_15m, reference_1h, reference_24h] =
+            [5, 15, 60, 24 * 60].map(|minutes| {
+                Self::reference_at(
+                    pool_history,
+                    latest,
+                    latest.timestamp.minus(minutes * minute),
+                )
+            });
+        let mut changes = Self::changes_from_references(
+            latest,
+            reference_5m.as_ref().map(|(record, _)| record),
+            reference_15m.as_ref().map(|(record, _)| record),
+            reference_1h.as_ref().map(|(record, _)| record),
+            reference_24h.as_ref().map(|(record, _)| record),
+        );
+        changes.reference_age_5m = reference_5m.map(|(_, age)| age);
+        changes.reference_age_15m = reference_15m.map(|(_, age)| age);
+        changes.reference_age_1h = reference_1h.map(|(_, age)| age);
+        changes.reference_age_24h = reference_24h.map(|(_, age)| age);
+        Some(changes)
+    }
+
+    // target 时间点的参考数据：在前后两条记录之间线性插值，之后没有记录时使用之前最近的一条；
+    // 同时返回之前那条记录距最新记录的时长。target 之前没有记录时返回 None
+    fn reference_at<T: Timestamp>(
+        pool_history: &VecDeque<HistoricalData<T>>,
+        latest: &HistoricalData<T>,
+        target: T,
+    ) -> Option<(HistoricalData<T>, Duration)> {
+        let index = pool_history.partition_point(|r| r.timestamp <= target);
+        let before = pool_history.get(index.checked_sub(1)?)?;
+        let age = latest.timestamp.since(before.timestamp);
+        let Some(after) = pool_history.get(index) else {
+            return Some((before.clone(), age));
+        };
+
+        let ratio = target.since(before.timestamp).as_secs_f64()
+            / after.timestamp.since(before.timestamp).as_secs_f64();
+        let lerp = |from: f64, to: f64| from + (to - from) * ratio;
+        // APR 和储备为 0 表示缺少数据，只在两侧都有数据时插值
+        let lerp_present = |from: f64, to: f64| {
+            if from > 0.0 && to > 0.0 {
+                lerp(from, to)
+            } else {
+                from
+            }
+        };
+        let record = HistoricalData {
+            volume_24h: lerp(before.volume_24h, after.volume_24h),
+            volume_7d: before.volume_7d,
+            price: lerp(before.price, after.price),
+            tvl: lerp(before.tvl, after.tvl),
+            apr: lerp_present(before.apr, after.apr),
+            reserve_a: lerp_present(before.reserve_a, after.reserve_a),
+            reserve_b: lerp_present(before.reserve_b, after.reserve_b),
+            timestamp: target,
+        };
+        Some((record, age))
+    }
+
+    // 二分查找不晚于 target 的最后一条记录，历史记录按时间顺序追加，因此是有序的
+    fn record_at_or_before<T: Timestamp>(
+        pool_history: &VecDeque<HistoricalData<T>>,
+        target: T,
+    ) -> Option<&HistoricalData<T>> {
+        let index = pool_history.partition_point(|r| r.timestamp <= target);
+        index.checked_sub(1).and_then(|i| pool_history.get(i))
+    }
+
+    // 动量分数，范围 [-100, 100]：
+    // score = 100 * (w1*tanh(p5m/2) + w2*tanh(p1h/5) + w3*tanh(v1h/50)) / (w1+w2+w3)
+    // 价格和交易量都带方向（上涨、放量为正，下跌、缩量为负），暴跌的池子排在最后；
+    // 缺少的窗口按 0 计入，所有窗口都缺少时返回 None
+    pub async fn momentum_score(&self, pool_id: &str) -> Option<f64> {
+        let changes = self.get_changes(pool_id).await?;
+        if changes.price_change_5m.is_none()
+            && changes.price_change_1h.is_none()
+            && changes.volume_change_1h.is_none()
+        {
+            return None;
+        }
+
+        let normalized = |change: Option<f64>, scale: f64| (change.unwrap_or(0.0) / scale).tanh();
+        let weights = &self.momentum_weights;
+        let total = weights.price_5m + weights.price_1h + weights.volume_1h;
+        if total <= 0.0 {
+            return None;
+        }
+        let score = weights.price_5m * normalized(changes.price_change_5m, MOMENTUM_SCALE_PRICE_5M)
+            + weights.price_1h * normalized(changes.price_change_1h, MOMENTUM_SCALE_PRICE_1H)
+            + weights.volume_1h * normalized(changes.volume_change_1h, MOMENTUM_SCALE_VOLUME_1H);
+        Some(100.0 * score / total)
+    }
+
+    // 按动量分数从高到低排序，没有分数的池子排在最后（保持原有顺序）
+    pub async fn sort_by_momentum(&self, pools: &mut Vec<PoolInfo>) {
+        let mut scored = Vec::with_capacity(pools.len());
+        for pool_info in pools.drain(..) {
+            let score = self.momentum_score(&pool_info.id).await;
+            scored.push((score, pool_info));
+        }
+        scored.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) => b.total_cmp(a),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+        pools.extend(scored.into_iter().map(|(_, pool_info)| pool_info));
+    }
+
+    // 5分钟价格涨幅最大的 gainers 个和跌幅最大的 losers 个池子（数量为 0 的一侧为 None），
+    // 24小时交易量低于 min_volume 或没有5分钟变化的池子不参与，避免低流动性池子的噪音
+    pub async fn top_movers<'a>(
+        &self,
+        pools: &'a [PoolInfo],
+        gainers: usize,
+        losers: usize,
+        min_volume: f64,
+    ) -> TopMovers<'a> {
+        let mut candidates = Vec::new();
+        for pool_info in pools.iter().filter(|p| p.volume_24h >= min_volume) {
+            let change = self
+                .get_changes(&pool_info.id)
+                .await
+                .and_then(|changes| changes.price_change_5m)
+                .filter(|change| change.is_finite());
+            if let Some(change) = change {
+                candidates.push((pool_info, change));
+            }
+        }
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        TopMovers {
+            gainers: (gainers > 0).then(|| {
+                candidates
+                    .iter()
+                    .filter(|(_, change)| *change > 0.0)
+                    .take(gainers)
+                    .copied()
+                    .collect()
+            }),
+            losers: (losers > 0).then(|| {
+                candidates
+                    .iter()
+                    .rev()
+                    .filter(|(_, change)| *change < 0.0)
+                    .take(losers)
+                    .copied()
+                    .collect()
+            }),
+        }
+    }
+
+    // 最近两条记录之间代币 A/B 储备的变化率(%)，缺少储备数据时返回 None
+    pub async fn reserve_shift(&self, pool_id: &str) -> Option<(f64, f64)> {
+        let historical_data = self.historical_data.lock().await;
+        let pool_history = historical_data.get(pool_id)?;
+        let mut records = pool_history.iter().rev();
+        let latest = records.next()?;
+        let previous = records.next()?;
+        if [
+            latest.reserve_a,
+            latest.reserve_b,
+            previous.reserve_a,
+            previous.reserve_b,
+        ]
+        .iter()
+        .any(|reserve| *reserve <= 0.0)
+        {
+            return None;
+        }
+        Some((
+            Self::calculate_change(previous.reserve_a, latest.reserve_a),
+            Self::calculate_change(previous.reserve_b, latest.reserve_b),
+        ))
+    }
+
+    // 离 target 最近的记录（前后两侧取时间差较小的一条）
+    fn nearest_record(
+        pool_history: &PoolHistory,
+        target: DateTime<Utc>,
+    ) -> Option<&HistoricalData> {
+        let index = pool_history.partition_point(|r| r.timestamp <= target);
+        let before = index.checked_sub(1).and_then(|i| pool_history.get(i));
+        let after = pool_history.get(index);
+        match (before, after) {
+            (Some(before), Some(after)) => {
+                if target - before.timestamp <= after.timestamp - target {
+                    Some(before)
+                } else {
+                    Some(after)
+                }
+            }
+            (before, after) => before.or(after),
+        }
+    }
+
+    // 两个池子最近 window 内价格序列的皮尔逊相关系数：只取两者历史重叠的时间段，
+    // 以 pool_a 的每条记录为基准对齐 pool_b 中时间最接近的记录。
+    // 对齐的样本少于 MIN_CORRELATION_SAMPLES 或某一侧价格没有波动时返回 None
+    pub async fn correlation(
+        &self,
+        pool_a: &str,
+        pool_b: &str,
+        window: chrono::Duration,
+    ) -> Option<f64> {
+        let historical_data = self.historical_data.lock().await;
+        let history_a = historical_data.get(pool_a)?;
+        let history_b = historical_data.get(pool_b)?;
+
+        let end = history_a.back()?.timestamp.min(history_b.back()?.timestamp);
+        let start = (end - window)
+            .max(history_a.front()?.timestamp)
+            .max(history_b.front()?.timestamp);
+        let pairs: Vec<(f64, f64)> = history_a
+            .iter()
+            .filter(|record| record.timestamp >= start && record.timestamp <= end)
+            .filter_map(|record| {
+                let nearest = Self::nearest_record(history_b, record.timestamp)?;
+                Some((record.price, nearest.price))
+            })
+            .collect();
+        pearson_correlation(&pairs)
+    }
+
+    // 任意两个时间点之间某个指标的变化率(%)，两端分别取最近的记录；
+    // 两端落在同一条记录上（例如区间内没有数据）时返回 None
+    pub async fn get_change_between(
+        &self,
+        pool_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        metric: HistoryMetric,
+    ) -> Option<f64> {
+        if from >= to {
+            return None;
+        }
+        let historical_data = self.historical_data.lock().await;
+        let pool_history = historical_data.get(pool_id)?;
+
+        let start = Self::nearest_record(pool_history, from)?;
+        let end = Self::nearest_record(pool_history, to)?;
+        if start.timestamp >= end.timestamp {
+            return None;
+        }
+        Some(Self::calculate_change(
+            metric.value(start),
+            metric.value(end),
+        ))
+    }
+
+    // 平滑版本：参考值为回看时间点附近窗口内记录的时间加权平均，
+    // 离目标时间越近权重越大，减少采样间隔不均匀带来的抖动
+    pub async fn get_changes_smoothed(&self, pool_id: &str) -> Option<ChangeMetrics> {
+        let historical_data = self.historical_data.lock().await;
+        let pool_history = historical_data.get(pool_id)?;
+        let latest = pool_history.back()?;
+
+        let reference = |lookback: chrono::Duration| {
+            Self::weighted_reference(pool_history, latest.timestamp - lookback, lookback / 5)
+        };
+        let record_5m = reference(chrono::Duration::minutes(5));
+        let record_15m = reference(chrono::Duration::minutes(15));
+        let record_1h = reference(chrono::Duration::hours(1));
+        let record_24h = reference(chrono::Duration::hours(24));
+
+        Some(Self::changes_from_references(
+            latest,
+            record_5m.as_ref(),
+            record_15m.as_ref(),
+            record_1h.as_ref(),
+            record_24h.as_ref(),
+        ))
+    }
+
+    // 目标时间前后 window 内记录的指数衰减加权平均，窗口内没有记录时返回 None
+    fn weighted_reference(
+        pool_history: &PoolHistory,
+        target: DateTime<Utc>,
+        window: chrono::Duration,
+    ) -> Option<HistoricalData> {
+        // 衰减时间常数取窗口的一半
+        let tau = (window.num_milliseconds() as f64 / 2.0).max(1.0);
+        let mut total_weight = 0.0;
+        let (mut volume_24h, mut price, mut tvl, mut apr) = (0.0, 0.0, 0.0, 0.0);
+        let (mut reserve_a, mut reserve_b) = (0.0, 0.0);
+        let mut nearest: Option<&HistoricalData> = None;
+
+        for record in pool_history {
+            let distance = (record.timestamp - target).num_milliseconds().abs();
+            if distance > window.num_milliseconds() {
+                continue;
+            }
+            let weight = (-(distance as f64) / tau).exp();
+            total_weight += weight;
+            volume_24h += record.volume_24h * weight;
+            price += record.price * weight;
+            tvl += record.tvl * weight;
+            apr += record.apr * weight;
+            reserve_a += record.reserve_a * weight;
+            reserve_b += record.reserve_b * weight;
+            if nearest.is_none_or(|n| (n.timestamp - target).num_milliseconds().abs() > distance) {
+                nearest = Some(record);
+            }
+        }
+
+        let nearest = nearest?;
+        Some(HistoricalData {
+            volume_24h: volume_24h / total_weight,
+            volume_7d: nearest.volume_7d,
+            price: price / total_weight,
+            tvl: tvl / total_weight,
+            apr: apr / total_weight,
+            reserve_a: reserve_a / total_weight,
+            reserve_b: reserve_b / total_weight,
+            timestamp: target,
+        })
+    }
+
+    // 根据各回看时间点的参考记录计算变化指标
+    fn changes_from_references<T>(
+        latest: &HistoricalData<T>,
+        record_5m: Option<&HistoricalData<T>>,
+        record_15m: Option<&HistoricalData<T>>,
+        record_1h: Option<&HistoricalData<T>>,
+        record_24h: Option<&HistoricalData<T>>,
+    ) -> ChangeMetrics {
+        ChangeMetrics {
+            volume_change_5m: record_5m
+                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h)),
+            volume_change_15m: record_15m
+                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h)),
+            volume_change_1h: record_1h
+                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h)),
+            // 没有24小时前的记录时，用7天日均交易量近似
+            volume_change_24h: record_24h
+                .map(|r| Self::calculate_change(r.volume_24h, latest.volume_24h))
+                .or_else(|| {
+                    latest
+                        .volume_7d
+                        .filter(|volume_7d| *volume_7d > 0.0)
+                        .map(|volume_7d| Self::calculate_change(volume_7d / 7.0, latest.volume_24h))
+                }),
+            price_change_5m: record_5m.map(|r| Self::calculate_change(r.price, latest.price)),
+            price_change_15m: record_15m.map(|r| Self::calculate_change(r.price, latest.price)),
+            price_change_1h: record_1h.map(|r| Self::calculate_change(r.price, latest.price)),
+            price_change_24h: record_24h.map(|r| Self::calculate_change(r.price, latest.price)),
+            tvl_change_5m: record_5m.map(|r| Self::calculate_change(r.tvl, latest.tvl)),
+            tvl_change_15m: record_15m.map(|r| Self::calculate_change(r.tvl, latest.tvl)),
+            tvl_change_1h: record_1h.map(|r| Self::calculate_change(r.tvl, latest.tvl)),
+            tvl_change_24h: record_24h.map(|r| Self::calculate_change(r.tvl, latest.tvl)),
+            // CSV 导入的记录没有 APR（为 0），此时不计算变化
+            apr_change_24h: record_24h
+                .filter(|r| r.apr > 0.0)
+                .map(|r| Self::calculate_change(r.apr, latest.apr)),
+            // 同样跳过没有储备数据的记录
+            reserve_a_change_5m: record_5m
+                .filter(|r| r.reserve_a > 0.0 && latest.reserve_a > 0.0)
+                .map(|r| Self::calculate_change(r.reserve_a, latest.reserve_a)),
+            reserve_b_change_5m: record_5m
+                .filter(|r| r.reserve_b > 0.0 && latest.reserve_b > 0.0)
+                .map(|r| Self::calculate_change(r.reserve_b, latest.reserve_b)),
+            reference_age_5m: None,
+            reference_age_15m: None,
+            reference_age_1h: None,
+            reference_age_24h: None,
+        }
+    }
+
+    // 修正后的更新历史数据方法
+    #[tracing::instrument(
+        skip(self, pool_info),
+        fields(
+            pool_id = %pool_info.id,
+            symbols = %pool_info.pair_name()
+        )
+    )]
+    pub async fn update_historical_data(&self, pool_info: &PoolInfo) {
+        let mut historical_data = self.historical_data.lock().await;
+        let pool_history = historical_data
+            .entry(pool_info.id.clone())
+            .or_insert_with(PoolHistory::new);
+
+        // 添加新的历史记录，包含市值数据
+        Self::push_record(
+            pool_history,
+            self.capacity,
+            self.retention,
+            HistoricalData {
+                volume_24h: pool_info.volume_24h,
+                volume_7d: pool_info.volume_7d,
+                price: pool_info.price,
+                tvl: pool_info.tvl,
+                apr: pool_info.apr_24h,
+                reserve_a: pool_info.reserve_a,
+                reserve_b: pool_info.reserve_b,
+                timestamp: pool_info.timestamp,
+            },
+        );
+
+        // 可选：输出调试信息
+        tracing::debug!(records = pool_history.len(), "Updated historical data");
+    }
+
+    // 追加一条记录（需按时间顺序），并执行去重、过期清理和降采样
+    fn push_record(
+        pool_history: &mut PoolHistory,
+        capacity: Option<usize>,
+        retention: chrono::Duration,
+        record: HistoricalData,
+    ) {
+        // 数据与上一条记录完全相同且间隔未超过上限时不重复记录
+        if let Some(last) = pool_history.back() {
+            if last.volume_24h == record.volume_24h
+                && last.price == record.price
+                && last.tvl == record.tvl
+                && last.reserve_a == record.reserve_a
+                && last.reserve_b == record.reserve_b
+                && record.timestamp - last.timestamp < history_dedup_max_gap()
+            {
+                tracing::trace!("Skipped unchanged historical record");
+                return;
+            }
+        }
+
+        let latest = record.timestamp;
+        if let Some(capacity) = capacity {
+            while pool_history.len() >= capacity {
+                pool_history.pop_front();
+            }
+        }
+        pool_history.push_back(record);
+
+        // 只保留保留期内的数据
+        let cutoff = timestamp::now() - retention;
+        pool_history.retain(|record| record.timestamp > cutoff);
+
+        // 记录过多时压缩较早的数据
+        if pool_history.len() > HISTORY_COMPACT_THRESHOLD {
+            Self::downsample(pool_history, latest);
+            tracing::debug!(records = pool_history.len(), "Downsampled historical data");
+        }
+    }
+
+    // 从 CSV 文件导入历史快照（列：pool_id,timestamp,price,volume_24h,tvl），
+    // 返回实际保留的新记录数（去重、超出保留期或容量被丢弃的行不计入）。
+    // 与已有记录合并后按时间顺序重新写入，保证 get_changes 的查找仍然有效
+    pub async fn ingest_csv(&self, path: &Path) -> Result<usize> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read CSV file {}", path.display()))?;
+
+        let mut reader = csv::Reader::from_reader(content.as_bytes());
+        let mut rows: HashMap<String, Vec<HistoricalData>> = HashMap::new();
+        let mut parsed = 0;
+        for (line, row) in reader.deserialize::<CsvSnapshot>().enumerate() {
+            let row = row.with_context(|| format!("Invalid CSV row {}", line + 2))?;
+            rows.entry(row.pool_id).or_default().push(HistoricalData {
+                volume_24h: row.volume_24h,
+                volume_7d: None,
+                price: row.price,
+                tvl: row.tvl,
+                apr: 0.0,
+                reserve_a: 0.0,
+                reserve_b: 0.0,
+                timestamp: row.timestamp,
+            });
+            parsed += 1;
+        }
+
+        let mut historical_data = self.historical_data.lock().await;
+        let mut retained = 0;
+        for (pool_id, mut records) in rows {
+            let pool_history = historical_data.entry(pool_id).or_default();
+            let existing: HashSet<DateTime<Utc>> =
+                pool_history.iter().map(|record| record.timestamp).collect();
+            let ingested: HashSet<DateTime<Utc>> =
+                records.iter().map(|record| record.timestamp).collect();
+            records.extend(pool_history.drain(..));
+            records.sort_by_key(|record| record.timestamp);
+            for record in records {
+                Self::push_record(pool_history, self.capacity, self.retention, record);
+            }
+            retained += pool_history
+                .iter()
+                .filter(|record| {
+                    ingested.contains(&record.timestamp) && !existing.contains(&record.timestamp)
+                })
+                .count();
+        }
+
+        tracing::info!(
+            "Ingested {} historical records from {} ({} rows parsed)",
+            retained,
+            path.display(),
+            parsed
+        );
+        Ok(retained)
+    }
+
+    // 降采样：最近1小时保留全部记录，1-6小时按1分钟、6-48小时按5分钟、更早按1小时分桶，
+    // 每个桶保留最后一条记录，保证 5m/15m/1h/24h 的回看仍然准确
+    fn downsample(pool_history: &mut PoolHistory, latest: DateTime<Utc>) {
+        let bucket = |record: &HistoricalData| {
+            let age = latest - record.timestamp;
+            let bucket_secs = if age < chrono::Duration::hours(1) {
+                return None;
+            } else if age < chrono::Duration::hours(6) {
+                60
+            } else if age < chrono::Duration::hours(48) {
+                300
+            } else {
+                3600
+            };
+            Some((
+                bucket_secs,
+                record.timestamp.timestamp().div_euclid(bucket_secs),
+            ))
+        };
+
+        let mut compacted = PoolHistory::with_capacity(pool_history.len());
+        for record in pool_history.drain(..) {
+            let key = bucket(&record);
+            if key.is_some() && compacted.back().and_then(bucket) == key {
+                compacted.pop_back();
+            }
+            compacted.push_back(record);
+        }
+        *pool_history = compacted;
+    }
+
+    // 将历史数据保存到 JSON 文件
+    pub async fn save_history(&self, path: &Path) -> Result<()> {
+        let historical_data = self.historical_data.lock().await;
+        let json = serde_json::to_string(&*historical_data)?;
+        tokio::fs::write(path, json)
+            .await
+            .with_context(|| format!("Failed to write history file {}", path.display()))?;
+        Ok(())
+    }
+
+    // 从 JSON 文件加载历史数据，覆盖内存中的记录
+    pub async fn load_history(&self, path: &Path) -> Result<()> {
+        let json = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read history file {}", path.display()))?;
+        let loaded: HashMap<String, PoolHistory> = serde_json::from_str(&json)?;
+        let mut historical_data = self.historical_data.lock().await;
+        *historical_data = loaded;
+        Ok(())
+    }
+
+    // 恒定乘积池从 since 到最新记录的无常损失(%)，公式 2*sqrt(r)/(1+r) - 1，r 为价格比
+    // since 之前没有记录时返回 None
+    pub async fn impermanent_loss(&self, pool_id: &str, since: DateTime<Utc>) -> Option<f64> {
+        let historical_data = self.historical_data.lock().await;
+        let pool_history = historical_data.get(pool_id)?;
+        let latest = pool_history.back()?;
+        let reference = Self::record_at_or_before(pool_history, since)?;
+        if reference.price <= 0.0 || latest.price <= 0.0 {
+            return None;
+        }
+
+        let ratio = latest.price / reference.price;
+        Some((2.0 * ratio.sqrt() / (1.0 + ratio) - 1.0) * 100.0)
+    }
+
+    // 最新24小时交易量相对于此前历史的 z-score，样本不足或无波动时返回 None
+    pub async fn volume_zscore(&self, pool_id: &str) -> Option<f64> {
+        let historical_data = self.historical_data.lock().await;
+        let pool_history = historical_data.get(pool_id)?;
+        let latest = pool_history.back()?;
+        let previous = pool_history.range(..pool_history.len() - 1);
+        if previous.len() < MIN_ZSCORE_SAMPLES {
+            return None;
+        }
+
+        let count = previous.len() as f64;
+        let mean = previous.clone().map(|r| r.volume_24h).sum::<f64>() / count;
+        let variance = previous.map(|r| (r.volume_24h - mean).powi(2)).sum::<f64>() / count;
+        let std_dev = variance.sqrt();
+        if std_dev == 0.0 {
+            return None;
+        }
+
+        Some((latest.volume_24h - mean) / std_dev)
+    }
+
+    // 统计所有池子的历史记录数量和时间范围
+    pub async fn memory_report(&self) -> HistoryStats {
+        let historical_data = self.historical_data.lock().await;
+        let mut stats = HistoryStats {
+            pools: historical_data.len(),
+            min_records_per_pool: usize::MAX,
+            ..HistoryStats::default()
+        };
+        for pool_history in historical_data.values() {
+            stats.total_records += pool_history.len();
+            stats.min_records_per_pool = stats.min_records_per_pool.min(pool_history.len());
+            stats.max_records_per_pool = stats.max_records_per_pool.max(pool_history.len());
+            // 每个池子的记录按时间排序，只需比较首尾
+            if let Some(first) = pool_history.front() {
+                stats.oldest = Some(
+                    stats
+                        .oldest
+                        .map_or(first.timestamp, |oldest| oldest.min(first.timestamp)),
+                );
+            }
+            if let Some(last) = pool_history.back() {
+                stats.newest = stats.newest.max(Some(last.timestamp));
+            }
+        }
+        if stats.pools == 0 {
+            stats.min_records_per_pool = 0;
+        } else {
+            stats.avg_records_per_pool = stats.total_records as f64 / stats.pools as f64;
+        }
+        stats.estimated_bytes = stats.total_records * std::mem::size_of::<HistoricalData>();
+        stats
+    }
+
+    // 获取某个池子最近 hours 小时的历史记录
+    pub async fn history_since(
+        &self,
+        pool_id: &str,
+        hours: i64,
+    ) -> error::Result<Vec<HistoricalData>> {
+        let historical_data = self.historical_data.lock().await;
+        let pool_history = historical_data
+            .get(pool_id)
+            .filter(|history| !history.is_empty())
+            .ok_or_else(|| MonitorError::NoHistory {
+                pool_id: pool_id.to_string(),
+            })?;
+
+        let since = timestamp::now() - chrono::Duration::hours(hours);
+        Ok(pool_history
+            .iter()
+            .filter(|record| record.timestamp >= since)
+            .cloned()
+            .collect())
+    }
+}
+
+pub async fn fetch_raydium_data(page: u32) -> error::Result<Value> {
+    raydium_client().fetch_pools(page).await
+}
+
+// 皮尔逊相关系数，样本不足或方差为 0 时返回 None
+fn pearson_correlation(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.len() < MIN_CORRELATION_SAMPLES {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_a = pairs.iter().map(|(a, _)| a).sum::<f64>() / n;
+    let mean_b = pairs.iter().map(|(_, b)| b).sum::<f64>() / n;
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (a, b) in pairs {
+        covariance += (a - mean_a) * (b - mean_b);
+        variance_a += (a - mean_a).powi(2);
+        variance_b += (b - mean_b).powi(2);
+    }
+    if variance_a <= 0.0 || variance_b <= 0.0 {
+        return None;
+    }
+    Some(covariance / (variance_a * variance_b).sqrt())
+}
+
+// 首先创建一个用于返回的数据结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolDataResult {
+    pub pools: Vec<PoolInfo>,
+    pub timestamp: DateTime<Utc>,
+    // 是否包含全部分页的数据（只取第一页时为 false）
+    pub complete: bool,
+}
+
+// 一次轮询中显示的池子的整体统计
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarketSummary {
+    pub pools: usize,
+    pub total_tvl: f64,
+    pub total_volume_24h: f64,
+    // 5分钟内上涨/下跌的池子数（没有5分钟历史的池子不计入）
+    pub up_5m: usize,
+    pub down_5m: usize,
+    // 有5分钟历史的池子的平均价格变化(%)
+    pub avg_price_change_5m: Option<f64>,
+}
+
+// 判断一批数据是否明显异常（上游故障）的阈值
+#[derive(Debug, Clone, Copy)]
+pub struct AnomalyThresholds {
+    // 异常池子占比(%)超过该值时判定整批异常，0 表示不检查
+    pub max_ratio: f64,
+    // 池子数少于该值时不检查，避免少量池子误判
+    pub min_pools: usize,
+}
+
+impl PoolDataResult {
+    // 过滤池子后重新选出交易量最高的前 top_n 个：抓取时只保证前 top_n 个有序，
+    // 前缀中的池子被过滤掉后，显示列表会由未排序的部分补齐
+    pub fn retain_pools(&mut self, top_n: usize, keep: impl FnMut(&PoolInfo) -> bool) {
+        let before = self.pools.len();
+        self.pools.retain(keep);
+        if self.pools.len() < before {
+            sort_top_n_by_volume(&mut self.pools, top_n);
+        }
+    }
+
+    // 检查整批数据是否异常：价格为0或非有限值、TVL 为负的池子占比过高，
+    // 或过多池子报告完全相同的价格。返回异常原因，正常时返回 None
+    pub fn detect_anomaly(&self, thresholds: &AnomalyThresholds) -> Option<String> {
+        let total = self.pools.len();
+        if thresholds.max_ratio <= 0.0 || total == 0 || total < thresholds.min_pools {
+            return None;
+        }
+        let exceeds = |count: usize| count as f64 / total as f64 * 100.0 > thresholds.max_ratio;
+
+        let invalid = self
+            .pools
+            .iter()
+            .filter(|pool_info| {
+                !pool_info.price.is_finite()
+                    || pool_info.price <= 0.0
+                    || !pool_info.tvl.is_finite()
+                    || pool_info.tvl < 0.0
+            })
+            .count();
+        if exceeds(invalid) {
+            return Some(format!(
+                "{} of {} pools have a zero/invalid price or negative TVL",
+                invalid, total
+            ));
+        }
+
+        let mut price_counts: HashMap<u64, usize> = HashMap::new();
+        for pool_info in &self.pools {
+            *price_counts.entry(pool_info.price.to_bits()).or_default() += 1;
+        }
+        let (price, identical) = price_counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(bits, count)| (f64::from_bits(bits), count))?;
+        if identical > 1 && exceeds(identical) {
+            return Some(format!(
+                "{} of {} pools report the identical price {}",
+                identical, total, price
+            ));
+        }
+        None
+    }
+
+    // 前 top_n 个池子的整体统计，5分钟变化来自 pool_monitor 的历史数据
+    pub async fn aggregate_stats(&self, pool_monitor: &PoolMonitor, top_n: usize) -> MarketSummary {
+        let mut summary = MarketSummary::default();
+        let mut price_changes = Vec::new();
+        for pool_info in self.pools.iter().take(display_limit(top_n)) {
+            summary.pools += 1;
+            summary.total_tvl += pool_info.tvl;
+            summary.total_volume_24h += pool_info.volume_24h;
+
+            let change = pool_monitor
+                .get_changes(&pool_info.id)
+                .await
+                .and_then(|changes| changes.price_change_5m);
+            if let Some(change) = change {
+                if change > 0.0 {
+                    summary.up_5m += 1;
+                } else if change < 0.0 {
+                    summary.down_5m += 1;
+                }
+                price_changes.push(change);
+            }
+        }
+        if !price_changes.is_empty() {
+            summary.avg_price_change_5m =
+                Some(price_changes.iter().sum::<f64>() / price_changes.len() as f64);
+        }
+        summary
+    }
+
+    // 按交易量排名（从 1 开始），不依赖 pools 的排序
+    pub fn rank_by_volume(&self, pool_id: &str) -> Option<usize> {
+        self.rank_by(pool_id, |pool_info| Some(pool_info.volume_24h))
+    }
+
+    pub fn rank_by_tvl(&self, pool_id: &str) -> Option<usize> {
+        self.rank_by(pool_id, |pool_info| Some(pool_info.tvl))
+    }
+
+    // 只在已计算市值的池子之间排名
+    pub fn rank_by_market_cap(&self, pool_id: &str) -> Option<usize> {
+        self.rank_by(pool_id, |pool_info| pool_info.market_cap)
+    }
+
+    // 交易量百分位：低于该池子的池子所占比例(%)
+    pub fn percentile_by_volume(&self, pool_id: &str) -> Option<f64> {
+        self.percentile_by(pool_id, |pool_info| Some(pool_info.volume_24h))
+    }
+
+    pub fn percentile_by_tvl(&self, pool_id: &str) -> Option<f64> {
+        self.percentile_by(pool_id, |pool_info| Some(pool_info.tvl))
+    }
+
+    pub fn percentile_by_market_cap(&self, pool_id: &str) -> Option<f64> {
+        self.percentile_by(pool_id, |pool_info| pool_info.market_cap)
+    }
+
+    fn rank_by(&self, pool_id: &str, metric: fn(&PoolInfo) -> Option<f64>) -> Option<usize> {
+        let value = metric(self.pools.iter().find(|p| p.id == pool_id)?)?;
+        let higher = self
+            .pools
+            .iter()
+            .filter_map(metric)
+            .filter(|other| *other > value)
+            .count();
+        Some(higher + 1)
+    }
+
+    fn percentile_by(&self, pool_id: &str, metric: fn(&PoolInfo) -> Option<f64>) -> Option<f64> {
+        let value = metric(self.pools.iter().find(|p| p.id == pool_id)?)?;
+        let values: Vec<f64> = self.pools.iter().filter_map(metric).collect();
+        if values.len() <= 1 {
+            return Some(100.0);
+        }
+        let lower = values.iter().filter(|other| **other < value).count();
+        Some(lower as f64 / (values.len() - 1) as f64 * 100.0)
+    }
+}
+
+pub async fn check_raydium_pools() -> error::Result<PoolDataResult> {
+    check_raydium_pools_pages(1, 0).await
+}
+
+// 按交易量降序排列前 top_n 个池子，其余池子保留但不排序（top_n 为 0 时全部排序）
+// 先用 select_nth_unstable_by 做 O(n) 的部分选择，再只对前 top_n 个排序
+pub fn sort_top_n_by_volume(pools: &mut [PoolInfo], top_n: usize) {
+    let by_volume = |a: &PoolInfo, b: &PoolInfo| b.volume_24h.total_cmp(&a.volume_24h);
+    if top_n == 0 || top_n >= pools.len() {
+        pools.sort_by(by_volume);
+        return;
+    }
+    pools.select_nth_unstable_by(top_n - 1, by_volume);
+    pools[..top_n].sort_by(by_volume);
+}
+
+// 并发获取前 pages 页数据，合并去重后按交易量排序（只保证前 top_n 个有序）
+// 单页失败只记录警告（429 已由客户端重试），全部失败时返回错误
+pub async fn check_raydium_pools_pages(pages: u32, top_n: usize) -> error::Result<PoolDataResult> {
+    check_raydium_pools_pages_with_dump(pages, top_n, None).await
+}
+
+// 同 check_raydium_pools_pages，dump 不为空时先把每页的原始响应保存下来再解析
+pub async fn check_raydium_pools_pages_with_dump(
+    pages: u32,
+    top_n: usize,
+    dump: Option<&RawDump>,
+) -> error::Result<PoolDataResult> {
+    let current_time = Utc::now();
+    tracing::info!("Checking Raydium pools at {}", current_time);
+
+    let start = std::time::Instant::now();
+    let mut results: Vec<(u32, error::Result<PoolDataResult>)> = stream::iter(1..=pages.max(1))
+        .map(|page| async move {
+            let result = fetch_raydium_data(page).await.and_then(|data| {
+                if let Some(dump) = dump {
+                    dump.write_or_warn(current_time, &format!("page{}", page), &data);
+                }
+                parse_raydium_pools(&data, current_time)
+            });
+            (page, result)
+        })
+        .buffer_unordered(MAX_PAGE_CONCURRENCY)
+        .collect()
+        .await;
+    // 按页码顺序合并，重复的池子保留先出现的
+    results.sort_by_key(|(page, _)| *page);
+
+    let mut seen = HashSet::new();
+    let mut pools = Vec::new();
+    let mut complete = true;
+    let mut last_error = None;
+    for (page, result) in results {
+        match result {
+            Ok(page_data) => {
+                // 只有最后一页能说明是否还有更多数据
+                if page == pages.max(1) {
+                    complete &= page_data.complete;
+                }
+                pools.extend(
+                    page_data
+                        .pools
+                        .into_iter()
+                        .filter(|pool_info| seen.insert(pool_info.id.clone())),
+                );
+            }
+            Err(e) => {
+                tracing::warn!("Failed to fetch Raydium pools page {}: {}", page, e);
+                complete = false;
+                last_error = Some(e);
+            }
+        }
+    }
+    if pools.is_empty() {
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+
+    // 按24小时交易量排序
+    sort_top_n_by_volume(&mut pools, top_n);
+    tracing::info!(
+        fetch_ms = start.elapsed().as_millis() as u64,
+        pools = pools.len(),
+        "Fetched Raydium pools"
+    );
+
+    Ok(PoolDataResult {
+        pools,
+        timestamp: current_time,
+        complete,
+    })
+}
+
+// 解析 Raydium 列表接口返回的数据，保持接口返回的顺序
+pub fn parse_raydium_pools(
+    data: &Value,
+    current_time: DateTime<Utc>,
+) -> error::Result<PoolDataResult> {
+    check_api_response(data)?;
+    if let Some(pools) = data["data"]["data"].as_array() {
+        let mut pool_infos: Vec<PoolInfo> = Vec::new();
+
+        for pool in pools {
+            let Some(pool_info) = parse_pool(pool, current_time) else {
+                continue;
+            };
+
+            // 过滤特定池
+            if pool_info.is_stable_pair() {
+                continue;
+            }
+            pool_infos.push(pool_info);
+        }
+
+        // 不在这里排序：多页合并后由调用方按需排序（只排前 top_n 个）
+        Ok(PoolDataResult {
+            pools: pool_infos,
+            timestamp: current_time,
+            complete: !data["data"]["hasNextPage"].as_bool().unwrap_or(false),
+        })
+    } else {
+        Err(MonitorError::parse("pool data"))
+    }
+}
+
+// 按池子ID获取数据，分批请求以避免 URL 过长
+// 明确指定的池子不做稳定交易对过滤
+pub async fn fetch_pools_by_ids(ids: &[String]) -> error::Result<Vec<PoolInfo>> {
+    fetch_pools_by_ids_with_dump(ids, Utc::now(), None).await
+}
+
+// 同 fetch_pools_by_ids，所有池子使用调用方给出的 current_time 作为时间戳
+pub async fn fetch_pools_by_ids_with_dump(
+    ids: &[String],
+    current_time: DateTime<Utc>,
+    dump: Option<&RawDump>,
+) -> error::Result<Vec<PoolInfo>> {
+    let mut pools = Vec::new();
+    for (batch_index, batch) in ids.chunks(MAX_IDS_PER_REQUEST).enumerate() {
+        let data = raydium_client().fetch_pools_by_ids(batch).await?;
+        if let Some(dump) = dump {
+            dump.write_or_warn(current_time, &format!("ids{}", batch_index + 1), &data);
+        }
+        let Some(entries) = data["data"].as_array() else {
+            return Err(MonitorError::parse("pool data"));
+        };
+        // 不存在的池子ID在返回数组中为 null
+        for (id, entry) in batch.iter().zip(entries) {
+            if entry.is_null() {
+                tracing::warn!("Pool {} not found", id);
+                continue;
+            }
+            pools.extend(parse_pool(entry, current_time));
+        }
+    }
+    Ok(pools)
+}
+
+// 解析单个池子，缺少必要字段时记录警告并返回 None
+fn parse_pool(pool: &Value, current_time: DateTime<Utc>) -> Option<PoolInfo> {
+    let (Some(id), Some(symbol_a_address), Some(symbol_b_address), Some(symbol_b_decimals)) = (
+        pool["id"].as_str(),
+        pool["mintA"]["address"].as_str(),
+        pool["mintB"]["address"].as_str(),
+        pool["mintB"]["decimals"].as_u64(),
+    ) else {
+        let missing: Vec<&str> = [
+            ("id", pool["id"].is_string()),
+            ("mintA.address", pool["mintA"]["address"].is_string()),
+            ("mintB.address", pool["mintB"]["address"].is_string()),
+            ("mintB.decimals", pool["mintB"]["decimals"].is_u64()),
+        ]
+        .into_iter()
+        .filter(|(_, present)| !present)
+        .map(|(field, _)| field)
+        .collect();
+        tracing::warn!(
+            "Skipping pool {}: missing {}",
+            pool["id"].as_str().unwrap_or("<unknown id>"),
+            missing.join(", ")
+        );
+        return None;
+    };
+
+    // 未验证的代币可能没有 symbol，使用 mint 地址代替
+    let symbol_or_address = |mint: &str, address: &str| -> String {
+        match pool[mint]["symbol"].as_str().filter(|s| !s.is_empty()) {
+            Some(symbol) => symbol.to_string(),
+            None => {
+                tracing::warn!("Pool {} has no {}.symbol, using the mint address", id, mint);
+                address.to_string()
+            }
+        }
+    };
+    let symbol_a = symbol_or_address("mintA", symbol_a_address);
+    let symbol_b = symbol_or_address("mintB", symbol_b_address);
+
+    let volume_24h = pool["day"]["volume"].as_f64().unwrap_or(0.0);
+    let volume_7d = pool["week"]["volume"].as_f64();
+    let volume_30d = pool["month"]["volume"].as_f64();
+    let tvl = pool["tvl"].as_f64().unwrap_or(0.0);
+    let reserve_a = pool["mintAmountA"].as_f64().unwrap_or(0.0);
+    let reserve_b = pool["mintAmountB"].as_f64().unwrap_or(0.0);
+    let burn_percent = pool["burnPercent"].as_f64().unwrap_or(0.0);
+    // openTime 是字符串形式的 unix 时间戳（秒），也兼容数字
+    let open_time = pool["openTime"]
+        .as_str()
+        .and_then(|open_time| open_time.parse::<i64>().ok())
+        .or_else(|| pool["openTime"].as_i64())
+        .filter(|open_time| *open_time > 0)
+        .and_then(|open_time| DateTime::from_timestamp(open_time, 0));
+    let lp_mint = pool["lpMint"]["address"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let price = pool["price"].as_f64().unwrap_or(0.0);
+    let price_b_in_a = if price > 0.0 { 1.0 / price } else { 0.0 };
+    let price_min_24h = pool["day"]["priceMin"].as_f64().unwrap_or(0.0);
+    let price_max_24h = pool["day"]["priceMax"].as_f64().unwrap_or(0.0);
+    let apr_24h = pool["day"]["apr"].as_f64().unwrap_or(0.0);
+    let fee_apr_24h = pool["day"]["feeApr"].as_f64().unwrap_or(0.0);
+    let pool_type = pool["type"].as_str().unwrap_or_default().to_string();
+    let pool_tags = pool["pooltype"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    let program_id = pool["programId"].as_str().unwrap_or_default().to_string();
+    let fee_rate = pool["feeRate"].as_f64().unwrap_or(0.0);
+    let volume_fee_24h = pool["day"]["volumeFee"].as_f64().unwrap_or(0.0);
+
+    Some(PoolInfo {
+        id: id.to_string(),
+        symbol_a,
+        symbol_a_address: symbol_a_address.to_string(),
+        symbol_b,
+        symbol_b_address: symbol_b_address.to_string(),
+        symbol_b_decimals,
+        symbol_a_decimals: pool["mintA"]["decimals"].as_u64().unwrap_or_default(),
+        pool_type,
+        pool_tags,
+        program_id,
+        volume_24h,
+        volume_7d,
+        volume_30d,
+        tvl,
+        reserve_a,
+        reserve_b,
+        burn_percent,
+        lp_mint,
+        open_time,
+        price,
+        price_a_in_b: price.max(0.0),
+        price_b_in_a,
+        price_min_24h,
+        price_max_24h,
+        apr_24h,
+        fee_apr_24h,
+        fee_rate,
+        volume_fee_24h,
+        market_cap: None,
+        market_cap_supply_age: None,
+        token_supply: None,
+        fdv_estimate: fdv_estimate(pool),
+        timestamp: current_time,
+    })
+}
+
+// 池子数据来源，便于替换为离线数据进行测试
+pub trait PoolDataSource: Send + Sync {
+    fn fetch(&self) -> Pin<Box<dyn Future<Output = Result<PoolDataResult>> + Send + '_>>;
+}
+
+// 从 Raydium HTTP 接口获取数据
+pub struct HttpSource {
+    // 获取的页数（每页100个池子）
+    pub pages: u32,
+    // 只需要排好序的前 top_n 个池子（0 表示全部）
+    pub top_n: usize,
+    // 保存原始响应（--dump-raw）
+    pub dump_raw: Option<RawDump>,
+}
+
+impl PoolDataSource for HttpSource {
+    fn fetch(&self) -> Pin<Box<dyn Future<Output = Result<PoolDataResult>> + Send + '_>> {
+        Box::pin(async move {
+            Ok(
+                check_raydium_pools_pages_with_dump(self.pages, self.top_n, self.dump_raw.as_ref())
+                    .await?,
+            )
+        })
+    }
+}
+
+// 只监控指定的池子
+pub struct IdsSource {
+    pub ids: Vec<String>,
+    pub dump_raw: Option<RawDump>,
+}
+
+impl PoolDataSource for IdsSource {
+    fn fetch(&self) -> Pin<Box<dyn Future<Output = Result<PoolDataResult>> + Send + '_>> {
+        Box::pin(async move {
+            // 快照和其中的池子使用同一个时间戳
+            let current_time = Utc::now();
+            let mut pools =
+                fetch_pools_by_ids_with_dump(&self.ids, current_time, self.dump_raw.as_ref())
+                    .await?;
+            pools.sort_by(|a, b| b.volume_24h.total_cmp(&a.volume_24h));
+            Ok(PoolDataResult {
+                pools,
+                timestamp: current_time,
+                complete: true,
+            })
+        })
+    }
+}
+
+// 从保存的 JSON 响应文件读取数据
+// 支持完整的列表接口响应、池子数组，或单个池子对象（同 utils 测试数据）
+pub struct FileSource {
+    pub path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileSource { path: path.into() }
+    }
+}
+
+impl PoolDataSource for FileSource {
+    fn fetch(&self) -> Pin<Box<dyn Future<Output = Result<PoolDataResult>> + Send + '_>> {
+        Box::pin(async move {
+            let json = tokio::fs::read_to_string(&self.path)
+                .await
+                .with_context(|| format!("Failed to read fixture {}", self.path.display()))?;
+            let data: Value = serde_json::from_str(&json)?;
+
+            let data = match data {
+                Value::Array(pools) => serde_json::json!({ "data": { "data": pools } }),
+                // 完整响应也带有 id（请求ID），以是否有 data 区分
+                Value::Object(ref object)
+                    if object.contains_key("id") && !object.contains_key("data") =>
+                {
+                    serde_json::json!({ "data": { "data": [data] } })
+                }
+                data => data,
+            };
+
+            // 文件中的池子可能是任意顺序，按交易量完整排序
+            let mut pool_data = parse_raydium_pools(&data, Utc::now())?;
+            sort_top_n_by_volume(&mut pool_data.pools, 0);
+            Ok(pool_data)
+        })
+    }
+}
+
+// 按顺序回放预先构造的快照，每次 fetch 返回下一个，时间戳由快照本身决定；
+// 用于不依赖网络和真实时间地测试监控循环，回放完后返回错误
+pub struct ReplaySource {
+    snapshots: std::sync::Mutex<VecDeque<PoolDataResult>>,
+}
+
+impl ReplaySource {
+    pub fn new(snapshots: impl IntoIterator<Item = PoolDataResult>) -> Self {
+        ReplaySource {
+            snapshots: std::sync::Mutex::new(snapshots.into_iter().collect()),
+        }
+    }
+
+    // 剩余未回放的快照数
+    pub fn remaining(&self) -> usize {
+        self.snapshots.lock().unwrap().len()
+    }
+}
+
+impl PoolDataSource for ReplaySource {
+    fn fetch(&self) -> Pin<Box<dyn Future<Output = Result<PoolDataResult>> + Send + '_>> {
+        let next = self.snapshots.lock().unwrap().pop_front();
+        Box::pin(async move { next.ok_or(anyhow::anyhow!("Replay source is exhausted")) })
+    }
+}
+
+// 并发计算池子市值，单个池子失败只记录日志并置为 None。
+// RPC 限流时沿用缓存的供应量，不逐个池子报错，只在进入和离开限流状态时各记录一次
+pub async fn fill_market_caps(
+    pools: &mut [PoolInfo],
+    client: &RpcClient,
+    sol_price_cache: &SharedSolPriceCache,
+    supply_cache: &SharedSupplyCache,
+    concurrency: usize,
+) {
+    // 先收集为拥有所有权的参数，使返回的 Future 满足 Send，可以在后台任务中运行
+    let requests: Vec<_> = pools
+        .iter()
+        .enumerate()
+        .map(|(index, pool_info)| {
+            let span = tracing::info_span!(
+                "market_cap",
+                pool_id = %pool_info.id,
+                symbols = %pool_info.pair_name()
+            );
+            let quote = TokenQuote::from_pool(
+                (&pool_info.symbol_a_address, pool_info.symbol_a_decimals),
+                (&pool_info.symbol_b_address, pool_info.symbol_b_decimals),
+                pool_info.price,
+            );
+            (index, pool_info.id.clone(), quote, span)
+        })
+        .collect();
+    let results: Vec<_> =
+        stream::iter(requests.into_iter().map(|(index, pool_id, quote, span)| {
+            async move {
+                // 没有价格或两侧都不是 SOL/稳定币时无法换算成美元
+                let Some(quote) = quote else {
+                    return (index, None, false);
+                };
+                match calculate_market_cap_with_supply_cache(
+                    &quote,
+                    client,
+                    sol_price_cache,
+                    supply_cache,
+                )
+                .await
+                {
+                    Ok((market_cap, total_supply, supply_age)) => (
+                        index,
+                        Some((market_cap, quote.ui_supply(total_supply), supply_age)),
+                        supply_age.is_some(),
+                    ),
+                    Err(e) if e.is_rate_limited() => (index, None, true),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to calculate market cap for pool {}: {}",
+                            pool_id,
+                            e
+                        );
+                        (index, None, false)
+                    }
+                }
+            }
+            .instrument(span)
+        }))
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let rate_limited = results.iter().filter(|(_, _, limited)| *limited).count();
+    for (index, market_cap, _) in results {
+        pools[index].market_cap = market_cap.map(|(market_cap, _, _)| market_cap);
+        pools[index].token_supply = market_cap.map(|(_, token_supply, _)| token_supply);
+        pools[index].market_cap_supply_age = market_cap.and_then(|(_, _, supply_age)| supply_age);
+    }
+
+    let mut supply_cache = supply_cache.lock().await;
+    if rate_limited > 0 && !supply_cache.rate_limited {
+        tracing::warn!(
+            "RPC rate limited, market caps stale ({} of {} pools using cached or no supply)",
+            rate_limited,
+            pools.len()
+        );
+    } else if rate_limited == 0 && supply_cache.rate_limited {
+        tracing::info!("RPC rate limit cleared, market caps are fresh again");
+    }
+    supply_cache.rate_limited = rate_limited > 0;
+}
+
+// 新上线池子的警报
+pub fn format_new_pools(new_pools: &[&PoolInfo]) -> String {
+    new_pools
+        .iter()
+        .map(|pool_info| {
+            format!(
+                "🆕 New pool listed: {} ({}) 💰 {}\n",
+                pool_info.id,
+                pool_info.pair_name(),
+                pool_info.price_label()
+            )
+        })
+        .collect()
+}
+
+// 新上线池子转换为发送给通知渠道的警报
+pub fn new_pool_alerts(new_pools: &[&PoolInfo]) -> Vec<Alert> {
+    new_pools
+        .iter()
+        .map(|pool_info| Alert {
+            pool_id: pool_info.id.clone(),
+            symbol_a: pool_info.symbol_a.clone(),
+            symbol_b: pool_info.symbol_b.clone(),
+            metric: AlertKind::NewPool,
+            value: pool_info.price,
+            threshold: 0.0,
+            summary: None,
+        })
+        .collect()
+}
+
+// 目标价穿越转换为发送给通知渠道的警报
+pub fn price_crossing_alerts(crossings: &[PriceCrossing]) -> Vec<Alert> {
+    crossings
+        .iter()
+        .map(|crossing| Alert {
+            pool_id: crossing.pool_id.clone(),
+            symbol_a: crossing.symbol_a.clone(),
+            symbol_b: crossing.symbol_b.clone(),
+            metric: AlertKind::PriceTarget,
+            value: crossing.price,
+            threshold: crossing.level,
+            summary: None,
+        })
+        .collect()
+}
+
+// 已下架池子转换为发送给通知渠道的警报，快照中已经没有交易对信息
+pub fn disappeared_pool_alerts(pool_ids: &[String]) -> Vec<Alert> {
+    pool_ids
+        .iter()
+        .map(|pool_id| Alert {
+            pool_id: pool_id.clone(),
+            symbol_a: String::new(),
+            symbol_b: String::new(),
+            metric: AlertKind::PoolDelisted,
+            value: 0.0,
+            threshold: 0.0,
+            summary: None,
+        })
+        .collect()
+}
+
+// 目标价穿越警报
+pub fn format_price_crossings(crossings: &[PriceCrossing]) -> String {
+    crossings
+        .iter()
+        .map(|crossing| {
+            format!(
+                "🎯 {} ({}/{}) price {} target ${}: now ${}\n",
+                crossing.pool_id,
+                crossing.symbol_a,
+                crossing.symbol_b,
+                if crossing.upward {
+                    "rose above"
+                } else {
+                    "fell below"
+                },
+                format_price(crossing.level),
+                format_price(crossing.price)
+            )
+        })
+        .collect()
+}
+
+// 已下架池子的警报
+pub fn format_disappeared_pools(pool_ids: &[String]) -> String {
+    pool_ids
+        .iter()
+        .map(|pool_id| format!("⚠️ Pool no longer listed: {}\n", pool_id))
+        .collect()
+}
+
+// 变化率，没有历史数据时显示 n/a
+// 开启颜色时上涨为绿色、下跌为红色，超过警报阈值时加粗
+// width 用于表格对齐，先补齐空格再着色，避免颜色控制符影响宽度
+fn format_change(change: Option<f64>, threshold: f64, color: bool, width: usize) -> String {
+    let Some(change) = change else {
+        return format!("{:>width$}", "n/a", width = width);
+    };
+    let text = format!("{:>width$}", format!("{:.2}%", change), width = width);
+    if !color {
+        return text;
+    }
+
+    let mut style = Style::new();
+    if change > 0.0 {
+        style = style.green();
+    } else if change < 0.0 {
+        style = style.red();
+    }
+    if change.abs() > threshold {
+        style = style.bold();
+    }
+    text.style(style).to_string()
+}
+
+// 池子没有历史时显示 n/a，某个时间窗口缺少数据时显示 --
+fn format_window_change(
+    changes: Option<&ChangeMetrics>,
+    metric: fn(&ChangeMetrics) -> Option<f64>,
+    threshold: f64,
+    color: bool,
+    width: usize,
+) -> String {
+    match changes.map(metric) {
+        Some(None) => format!("{:>width$}", "--", width = width),
+        change => format_change(change.flatten(), threshold, color, width),
+    }
+}
+
+// top_n 为 0 时表示不限制显示数量
+pub fn display_limit(top_n: usize) -> usize {
+    if top_n == 0 {
+        usize::MAX
+    } else {
+        top_n
+    }
+}
+
+// 池子年龄：不足1小时显示分钟，不足2天显示小时，否则显示天数
+pub fn format_age(age: chrono::Duration) -> String {
+    if age < chrono::Duration::hours(1) {
+        format!("{}m", age.num_minutes().max(0))
+    } else if age < chrono::Duration::days(2) {
+        format!("{}h", age.num_hours())
+    } else {
+        format!("{}d", age.num_days())
+    }
+}
+
+// 价格按有效数字显示：不足1000时保留4位有效数字并去掉末尾的0，
+// 1000及以上保留2位小数并加千位分隔符，极小的价格使用科学计数法
+pub fn format_price(price: f64) -> String {
+    if !price.is_finite() {
+        return "n/a".to_string();
+    }
+    if price == 0.0 {
+        return "0".to_string();
+    }
+    let abs = price.abs();
+    if abs < 1e-9 {
+        return format!("{:.3e}", price);
+    }
+
+    let decimals = (3 - abs.log10().floor() as i32).max(0);
+    let scale = 10f64.powi(decimals);
+    if (abs * scale).round() / scale < 1000.0 {
+        let text = format!("{:.*}", decimals as usize, price);
+        if !text.contains('.') {
+            return text;
+        }
+        return text.trim_end_matches('0').trim_end_matches('.').to_string();
+    }
+
+    let text = format!("{:.2}", abs);
+    let (integer, fraction) = text.split_once('.').unwrap_or((&text, "00"));
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    let sign = if price < 0.0 { "-" } else { "" };
+    format!("{}{}.{}", sign, grouped, fraction)
+}
+
+// 美元金额按 K/M/B 缩写，保留3位有效数字；四舍五入后进位时使用更大的单位
+pub fn humanize_usd(value: f64) -> String {
+    if !value.is_finite() {
+        return "n/a".to_string();
+    }
+    let sign = if value < 0.0 { "-" } else { "" };
+    let abs = value.abs();
+    if abs < 1_000.0 {
+        return format!("{}${:.2}", sign, abs);
+    }
+
+    const UNITS: [(f64, &str); 3] = [(1e3, "K"), (1e6, "M"), (1e9, "B")];
+    let mut unit = UNITS
+        .iter()
+        .rposition(|(size, _)| abs >= *size)
+        .unwrap_or(0);
+    loop {
+        let (size, suffix) = UNITS[unit];
+        let scaled = abs / size;
+        let decimals = (2 - scaled.max(1.0).log10().floor() as i32).max(0);
+        let factor = 10f64.powi(decimals);
+        if (scaled * factor).round() / factor >= 1000.0 && unit + 1 < UNITS.len() {
+            unit += 1;
+            continue;
+        }
+        return format!("{}${:.*}{}", sign, decimals as usize, scaled, suffix);
+    }
+}
+
+// 可选的美元金额，缺失时显示 n/a
+fn format_optional_usd(value: Option<f64>) -> String {
+    value.map(humanize_usd).unwrap_or_else(|| "n/a".to_string())
+}
+
+// 24小时价格区间，区间数据缺失时不显示
+fn format_price_range(pool_info: &PoolInfo) -> String {
+    if pool_info.price_max_24h <= 0.0 {
+        return String::new();
+    }
+    let position = pool_info
+        .price_range_position()
+        .map(|p| format!("{:.0}%", p))
+        .unwrap_or_else(|| "n/a".to_string());
+    format!(
+        "📏 Range: ${}–${} (current at {})\n",
+        format_price(pool_info.price_min_24h),
+        format_price(pool_info.price_max_24h),
+        position
+    )
+}
+
+// 警报阈值(%)
+#[derive(Debug, Clone, Copy)]
+pub struct AlertThresholds {
+    pub price_alert: f64,
+    pub volume_alert: f64,
+    // 短时间内 TVL 下降超过该比例时报警
+    pub tvl_drain_alert: f64,
+    // 交易量 z-score 超过该值时报警
+    pub volume_zscore_alert: f64,
+    // 两次轮询之间储备单边变化超过该比例时报警（通常是大额交易），0 表示关闭
+    pub reserve_alert: f64,
+    // LP 销毁比例低于该值时显示撤池风险提示，0 表示不提示
+    pub min_burn_percent: f64,
+    // 同一轮中同类警报超过该数量时合并为一条汇总，0 表示不合并
+    pub alert_aggregate: usize,
+    // 同一池子同一警报的冷却时间
+    pub alert_cooldown: chrono::Duration,
+    // --compare 的两个池子价格相关系数低于该值时报警，0 表示关闭
+    pub decouple_alert: f64,
+}
+
+// 与命令行参数的默认值一致
+impl Default for AlertThresholds {
+    fn default() -> Self {
+        AlertThresholds {
+            price_alert: 1.0,
+            volume_alert: 5.0,
+            tvl_drain_alert: 20.0,
+            volume_zscore_alert: 3.0,
+            reserve_alert: 0.0,
+            min_burn_percent: 0.0,
+            alert_aggregate: 0,
+            alert_cooldown: chrono::Duration::seconds(300),
+            decouple_alert: 0.0,
+        }
+    }
+}
+
+impl AlertThresholds {
+    // 某类警报对应的阈值
+    pub fn threshold(&self, kind: AlertKind) -> f64 {
+        match kind {
+            AlertKind::Price => self.price_alert,
+            AlertKind::Volume => self.volume_alert,
+            AlertKind::TvlDrain => self.tvl_drain_alert,
+            AlertKind::VolumeSpike => self.volume_zscore_alert,
+            AlertKind::ReserveShift => self.reserve_alert,
+            AlertKind::Decoupling => self.decouple_alert,
+            // 事件类警报没有可配置的阈值
+            AlertKind::NewPool | AlertKind::PoolDelisted | AlertKind::PriceTarget => 0.0,
+        }
+    }
+}
+
+// 添加一个格式化函数用于显示，同时返回本轮触发的警报供通知渠道发送。
+// alerts_enabled 为 false 时（例如本轮数据异常）完全不检查警报，也不会占用警报冷却时间
+pub async fn format_pool_data(
+    pool_data: &PoolDataResult,
+    pool_monitor: &PoolMonitor,
+    display: &DisplayOptions,
+    thresholds: &AlertThresholds,
+    alerts_enabled: bool,
+) -> (String, Vec<Alert>) {
+    let mut result = String::new();
+    result.push_str(&format!(
+        "🕒 Update time: {}\n\n",
+        pool_data.timestamp.format("%Y-%m-%d %H:%M:%S")
+    ));
+
+    let summary = pool_data.aggregate_stats(pool_monitor, display.top_n).await;
+    result.push_str(&format_market_summary(&summary));
+
+    if display.layout == Layout::Table {
+        result.push_str(&format_table_header());
+    }
+
+    // 先收集所有池子的警报，同类警报过多时合并为一条汇总
+    let mut rows = Vec::new();
+    for pool_info in pool_data.pools.iter().take(display_limit(display.top_n)) {
+        // 没有历史数据的池子也显示基本信息，变化率显示为 n/a
+        let changes = pool_monitor.get_changes(&pool_info.id).await;
+        let pool_thresholds = pool_monitor.thresholds_for(&pool_info.id, thresholds).await;
+        let impermanent_loss = pool_monitor
+            .impermanent_loss(
+                &pool_info.id,
+                pool_data.timestamp - chrono::Duration::hours(24),
+            )
+            .await;
+        let alerts = match &changes {
+            Some(changes) if alerts_enabled => {
+                check_alerts(
+                    pool_monitor,
+                    pool_info,
+                    changes,
+                    &pool_thresholds,
+                    pool_data.timestamp,
+                )
+                .await
+            }
+            _ => Vec::new(),
+        };
+        rows.push((
+            pool_info,
+            changes,
+            impermanent_loss,
+            alerts,
+            pool_thresholds,
+        ));
+    }
+
+    let mut alerts_by_kind: HashMap<AlertKind, Vec<(&PoolInfo, f64)>> = HashMap::new();
+    let mut notifications = Vec::new();
+    for (pool_info, _, _, alerts, _) in &rows {
+        for alert in alerts {
+            alerts_by_kind
+                .entry(alert.kind)
+                .or_default()
+                .push((*pool_info, alert.value));
+        }
+    }
+    let aggregated = |kind: AlertKind| {
+        thresholds.alert_aggregate > 0
+            && alerts_by_kind
+                .get(&kind)
+                .is_some_and(|alerts| alerts.len() > thresholds.alert_aggregate)
+    };
+    // 合并的警报只向通知渠道发送一条汇总，避免一轮收到大量相似的警报
+    for (pool_info, _, _, alerts, _) in &rows {
+        for alert in alerts.iter().filter(|alert| !aggregated(alert.kind)) {
+            notifications.push(Alert {
+                pool_id: pool_info.id.clone(),
+                symbol_a: pool_info.symbol_a.clone(),
+                symbol_b: pool_info.symbol_b.clone(),
+                metric: alert.kind,
+                value: alert.value,
+                threshold: alert.threshold,
+                summary: None,
+            });
+        }
+    }
+
+    let mut table_alerts = String::new();
+    for (pool_info, changes, impermanent_loss, alerts, pool_thresholds) in &rows {
+        let alerts = alerts.iter().filter(|alert| !aggregated(alert.kind));
+        match display.layout {
+            Layout::Detailed => {
+                result.push_str(&format_pool_detailed(
+                    pool_info,
+                    changes.as_ref(),
+                    *impermanent_loss,
+                    display,
+                    pool_thresholds,
+                ));
+                for alert in alerts {
+                    result.push_str(&alert.message);
+                }
+                result.push_str("----------------------\n");
+            }
+            Layout::Table => {
+                result.push_str(&format_table_row(
+                    pool_info,
+                    changes.as_ref(),
+                    display,
+                    pool_thresholds,
+                ));
+                for alert in alerts {
+                    table_alerts.push_str(&format!("{} {}", pool_info.pair_name(), alert.message));
+                }
+            }
+        }
+    }
+
+    for kind in [
+        AlertKind::Price,
+        AlertKind::Volume,
+        AlertKind::TvlDrain,
+        AlertKind::VolumeSpike,
+        AlertKind::ReserveShift,
+    ] {
+        if aggregated(kind) {
+            let summary = format_alert_summary(kind, &alerts_by_kind[&kind], thresholds);
+            notifications.push(Alert {
+                pool_id: top_alerted_pools(&alerts_by_kind[&kind])
+                    .iter()
+                    .map(|(pool_info, _)| pool_info.id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                symbol_a: String::new(),
+                symbol_b: String::new(),
+                metric: kind,
+                value: alerts_by_kind[&kind].len() as f64,
+                threshold: thresholds.threshold(kind),
+                summary: Some(summary.trim_end().to_string()),
+            });
+            table_alerts.push_str(&summary);
+        }
+    }
+
+    if !table_alerts.is_empty() {
+        result.push('\n');
+        result.push_str(&table_alerts);
+    }
+
+    (result, notifications)
+}
+
+// 整体市场概览，显示在池子列表之前
+fn format_market_summary(summary: &MarketSummary) -> String {
+    format!(
+        "🌐 Market: {} pools | TVL: {} | Vol(24h): {} | 5m: {}↑ {}↓ | Avg 5m: {}\n\n",
+        summary.pools,
+        humanize_usd(summary.total_tvl),
+        humanize_usd(summary.total_volume_24h),
+        summary.up_5m,
+        summary.down_5m,
+        summary
+            .avg_price_change_5m
+            .map(|change| format!("{:+.2}%", change))
+            .unwrap_or_else(|| "n/a".to_string()),
+    )
+}
+
+// 同类警报中变化最大的前3个池子
+fn top_alerted_pools<'a>(alerts: &[(&'a PoolInfo, f64)]) -> Vec<(&'a PoolInfo, f64)> {
+    let mut top = alerts.to_vec();
+    top.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+    top.truncate(3);
+    top
+}
+
+// 同类警报的汇总，列出变化最大的前3个池子
+fn format_alert_summary(
+    kind: AlertKind,
+    alerts: &[(&PoolInfo, f64)],
+    thresholds: &AlertThresholds,
+) -> String {
+    let top: Vec<String> = top_alerted_pools(alerts)
+        .iter()
+        .map(|(pool_info, value)| match kind {
+            AlertKind::VolumeSpike => format!("{} z={:.2}", pool_info.pair_name(), value),
+            _ => format!("{} {:+.2}%", pool_info.pair_name(), value),
+        })
+        .collect();
+
+    let headline = match kind {
+        AlertKind::Price => format!(
+            "📈 {} pools moved >{:.2}% in 5m",
+            alerts.len(),
+            thresholds.price_alert
+        ),
+        AlertKind::Volume => format!(
+            "📊 {} pools changed volume >{:.2}% in 5m",
+            alerts.len(),
+            thresholds.volume_alert
+        ),
+        AlertKind::TvlDrain => format!(
+            "🚨 {} pools lost >{:.2}% TVL",
+            alerts.len(),
+            thresholds.tvl_drain_alert
+        ),
+        AlertKind::VolumeSpike => format!(
+            "🚀 {} pools with volume spikes (z>{:.2})",
+            alerts.len(),
+            thresholds.volume_zscore_alert
+        ),
+        AlertKind::ReserveShift => format!(
+            "🐋 {} pools with one-sided reserve shifts >{:.2}%",
+            alerts.len(),
+            thresholds.reserve_alert
+        ),
+        AlertKind::Decoupling => format!(
+            "🔗 {} pool pairs decoupled (correlation <{:.2})",
+            alerts.len(),
+            thresholds.decouple_alert
+        ),
+        AlertKind::NewPool => format!("🆕 {} new pools listed", alerts.len()),
+        AlertKind::PoolDelisted => format!("⚠️ {} pools no longer listed", alerts.len()),
+        AlertKind::PriceTarget => format!("🎯 {} price targets crossed", alerts.len()),
+    };
+    format!("{}: TOP3 {}\n", headline, top.join(", "))
+}
+
+// 单个池子触发的警报，value 为触发时的变化率或 z-score
+#[derive(Debug, Clone)]
+pub struct PoolAlert {
+    pub kind: AlertKind,
+    pub value: f64,
+    // 触发时使用的阈值（可能是池子覆盖后的值）
+    pub threshold: f64,
+    pub message: String,
+}
+
+// 警报检查，返回需要显示的警报行
+async fn check_alerts(
+    pool_monitor: &PoolMonitor,
+    pool_info: &PoolInfo,
+    changes: &ChangeMetrics,
+    thresholds: &AlertThresholds,
+    now: DateTime<Utc>,
+) -> Vec<PoolAlert> {
+    let mut alerts = Vec::new();
+    let cooldown = thresholds.alert_cooldown;
+
+    let price_triggered = changes
+        .price_change_5m
+        .is_some_and(|change| change.abs() > thresholds.price_alert);
+    if pool_monitor
+        .should_alert(
+            &pool_info.id,
+            AlertKind::Price,
+            price_triggered,
+            now,
+            cooldown,
+        )
+        .await
+    {
+        let value = changes.price_change_5m.unwrap_or_default();
+        alerts.push(PoolAlert {
+            kind: AlertKind::Price,
+            threshold: thresholds.threshold(AlertKind::Price),
+            value,
+            message: format!("⚠️ 价格5分钟变化显著: {:.2}%\n", value),
+        });
+    }
+    let volume_triggered = changes
+        .volume_change_5m
+        .is_some_and(|change| change.abs() > thresholds.volume_alert);
+    if pool_monitor
+        .should_alert(
+            &pool_info.id,
+            AlertKind::Volume,
+            volume_triggered,
+            now,
+            cooldown,
+        )
+        .await
+    {
+        let value = changes.volume_change_5m.unwrap_or_default();
+        alerts.push(PoolAlert {
+            kind: AlertKind::Volume,
+            threshold: thresholds.threshold(AlertKind::Volume),
+            value,
+            message: format!("⚠️ 交易量5分钟变化显著: {:.2}%\n", value),
+        });
+    }
+    let (window, tvl_change) = changes.worst_short_term_tvl_change().unwrap_or(("5m", 0.0));
+    let tvl_triggered = tvl_change < -thresholds.tvl_drain_alert;
+    if pool_monitor
+        .should_alert(
+            &pool_info.id,
+            AlertKind::TvlDrain,
+            tvl_triggered,
+            now,
+            cooldown,
+        )
+        .await
+    {
+        alerts.push(PoolAlert {
+            kind: AlertKind::TvlDrain,
+            threshold: thresholds.threshold(AlertKind::TvlDrain),
+            value: tvl_change,
+            message: format!("🚨 TVL {}内骤降: {:.2}%\n", window, tvl_change),
+        });
+    }
+    let zscore = pool_monitor.volume_zscore(&pool_info.id).await;
+    let spike_triggered = zscore.is_some_and(|z| z > thresholds.volume_zscore_alert);
+    if pool_monitor
+        .should_alert(
+            &pool_info.id,
+            AlertKind::VolumeSpike,
+            spike_triggered,
+            now,
+            cooldown,
+        )
+        .await
+    {
+        let value = zscore.unwrap_or_default();
+        alerts.push(PoolAlert {
+            kind: AlertKind::VolumeSpike,
+            threshold: thresholds.threshold(AlertKind::VolumeSpike),
+            value,
+            message: format!("🚀 Volume spike: z-score {:.2}\n", value),
+        });
+    }
+    // 一侧储备增加、另一侧减少才是交易；两侧同向变化是添加/移除流动性
+    let shift = pool_monitor
+        .reserve_shift(&pool_info.id)
+        .await
+        .filter(|(change_a, change_b)| change_a * change_b < 0.0);
+    let shift_triggered = thresholds.reserve_alert > 0.0
+        && shift.is_some_and(|(change_a, change_b)| {
+            change_a.abs().max(change_b.abs()) > thresholds.reserve_alert
+        });
+    if pool_monitor
+        .should_alert(
+            &pool_info.id,
+            AlertKind::ReserveShift,
+            shift_triggered,
+            now,
+            cooldown,
+        )
+        .await
+    {
+        let (change_a, change_b) = shift.unwrap_or_default();
+        alerts.push(PoolAlert {
+            kind: AlertKind::ReserveShift,
+            threshold: thresholds.threshold(AlertKind::ReserveShift),
+            value: if change_a.abs() >= change_b.abs() {
+                change_a
+            } else {
+                change_b
+            },
+            message: format!(
+                "🐋 Reserve shift: {} {:+.2}% / {} {:+.2}%\n",
+                pool_info.symbol_a, change_a, pool_info.symbol_b, change_b
+            ),
+        });
+    }
+
+    alerts
+}
+
+// 详细布局：每个池子多行显示
+fn format_pool_detailed(
+    pool_info: &PoolInfo,
+    changes: Option<&ChangeMetrics>,
+    impermanent_loss: Option<f64>,
+    display: &DisplayOptions,
+    thresholds: &AlertThresholds,
+) -> String {
+    let change = |metric: fn(&ChangeMetrics) -> Option<f64>, threshold: f64| {
+        format_window_change(changes, metric, threshold, display.color, 0)
+    };
+    let price_alert = thresholds.price_alert;
+    let volume_alert = thresholds.volume_alert;
+    let tvl_alert = thresholds.tvl_drain_alert;
+
+    let mut result = format!(
+        "🔄 {} ({}) [{}]\n💰 {}\n",
+        pool_info.id,
+        pool_info.pair_name(),
+        pool_info.type_tag(),
+        pool_info.price_label(),
+    );
+    result.push_str(&format!(
+        "📈 Price: 5m:{} | 15m:{} | 1h:{} | 24h:{}",
+        change(|c| c.price_change_5m, price_alert),
+        change(|c| c.price_change_15m, price_alert),
+        change(|c| c.price_change_1h, price_alert),
+        change(|c| c.price_change_24h, price_alert),
+    ));
+    // 轮询中断后参考数据可能比窗口旧很多，提示变化率的可信度
+    if let Some(changes) = changes {
+        let stale: Vec<String> = changes
+            .stale_windows()
+            .into_iter()
+            .map(|(window, age)| {
+                let age = chrono::Duration::seconds(age.as_secs() as i64);
+                format!("{} change based on {}-old data", window, format_age(age))
+            })
+            .collect();
+        if !stale.is_empty() {
+            result.push_str(&format!(" ({})", stale.join(", ")));
+        }
+    }
+    result.push('\n');
+    result.push_str(&format!(
+        "📊 Vol: {} | 7d: {} | 30d: {}\n",
+        humanize_usd(pool_info.volume_24h),
+        format_optional_usd(pool_info.volume_7d),
+        format_optional_usd(pool_info.volume_30d),
+    ));
+    result.push_str(&format!(
+        "📊 Vol Chg: 5m:{} | 15m:{} | 1h:{} | 24h:{}\n",
+        change(|c| c.volume_change_5m, volume_alert),
+        change(|c| c.volume_change_15m, volume_alert),
+        change(|c| c.volume_change_1h, volume_alert),
+        change(|c| c.volume_change_24h, volume_alert),
+    ));
+    result.push_str(&format!(
+        "🏦 TVL: {}\n\
+         🏦 TVL Chg: 5m:{} | 15m:{} | 1h:{} | 24h:{}\n",
+        humanize_usd(pool_info.tvl),
+        change(|c| c.tvl_change_5m, tvl_alert),
+        change(|c| c.tvl_change_15m, tvl_alert),
+        change(|c| c.tvl_change_1h, tvl_alert),
+        change(|c| c.tvl_change_24h, tvl_alert),
+    ));
+    if display.impact_size > 0.0 {
+        result.push_str(&format!(
+            "💧 Impact({}): {:.2}%{}\n",
+            humanize_usd(display.impact_size),
+            pool_info.price_impact(display.impact_size),
+            if pool_info.is_constant_product() {
+                ""
+            } else {
+                " (est., constant-product approximation)"
+            }
+        ));
+    }
+    if let Some(age) = pool_info.age() {
+        result.push_str(&format!("⏱ {} old\n", format_age(age)));
+    }
+    result.push_str(&format!("🔥 LP burned: {:.2}%", pool_info.burn_percent));
+    if pool_info.burn_percent < thresholds.min_burn_percent {
+        result.push_str(&format!(
+            " ⚠️ below {:.2}% (rug risk)",
+            thresholds.min_burn_percent
+        ));
+    }
+    result.push('\n');
+    if pool_info.reserve_a > 0.0 || pool_info.reserve_b > 0.0 {
+        result.push_str(&format!(
+            "⚖️ Reserves: {:.2} {} / {:.2} {} (5m: {} / {})\n",
+            pool_info.reserve_a,
+            pool_info.symbol_a,
+            pool_info.reserve_b,
+            pool_info.symbol_b,
+            change(|c| c.reserve_a_change_5m, thresholds.reserve_alert),
+            change(|c| c.reserve_b_change_5m, thresholds.reserve_alert),
+        ));
+    }
+    result.push_str(&format!(
+        "🌾 APR: {:.2}% | Fee APR: {:.2}% | 24h Chg: {}\n",
+        pool_info.apr_24h,
+        pool_info.fee_apr_24h,
+        change(|c| c.apr_change_24h, f64::INFINITY),
+    ));
+    result.push_str(&format!(
+        "💵 Fees(24h): {} | Fee rate: {:.2}%",
+        humanize_usd(pool_info.volume_fee_24h),
+        pool_info.fee_rate * 100.0
+    ));
+    if pool_info.fee_apr_diverges() {
+        result.push_str(&format!(
+            " | ⚠️ est. fee APR {:.2}% vs reported {:.2}%, data may be stale",
+            pool_info.estimated_fee_apr().unwrap_or_default(),
+            pool_info.fee_apr_24h
+        ));
+    }
+    result.push('\n');
+    // 无常损失与 APR 一起显示，便于判断手续费能否覆盖
+    if let Some(impermanent_loss) = impermanent_loss {
+        result.push_str(&format!("🩹 IL(24h): {:.2}%\n", impermanent_loss));
+    }
+    result.push_str(&format_price_range(pool_info));
+    if let Some(market_cap) = pool_info.market_cap {
+        result.push_str(&format!("🏷️ MCap: {}", humanize_usd(market_cap)));
+        if let Some(age) = pool_info.market_cap_supply_age {
+            result.push_str(&format!(
+                " (stale: supply {} old, RPC rate limited)",
+                format_age(chrono::Duration::seconds(age.as_secs() as i64))
+            ));
+        }
+        if let Some(supply_in_pool) = pool_info.supply_in_pool_pct() {
+            result.push_str(&format!(" | 🏊 In pool: {:.2}% of supply", supply_in_pool));
+        }
+        result.push('\n');
+    }
+
+    result
+}
+
+// 表格布局中交易对名称的最大宽度
+const TABLE_PAIR_WIDTH: usize = 18;
+
+fn format_table_header() -> String {
+    format!(
+        "{:<width$} {:>16} {:>9} {:>9} {:>9} {:>12}\n",
+        "Pair",
+        "Price",
+        "5m",
+        "1h",
+        "24h",
+        "Vol(24h)",
+        width = TABLE_PAIR_WIDTH
+    )
+}
+
+// 表格布局：每个池子一行
+fn format_table_row(
+    pool_info: &PoolInfo,
+    changes: Option<&ChangeMetrics>,
+    display: &DisplayOptions,
+    thresholds: &AlertThresholds,
+) -> String {
+    let change = |metric: fn(&ChangeMetrics) -> Option<f64>| {
+        format_window_change(changes, metric, thresholds.price_alert, display.color, 9)
+    };
+
+    format!(
+        "{:<width$} {:>16} {} {} {} {:>12}\n",
+        truncate(&pool_info.pair_name(), TABLE_PAIR_WIDTH),
+        format_price(pool_info.price),
+        change(|c| c.price_change_5m),
+        change(|c| c.price_change_1h),
+        change(|c| c.price_change_24h),
+        humanize_usd(pool_info.volume_24h),
+        width = TABLE_PAIR_WIDTH
+    )
+}
+
+// 按字符截断过长的文本，末尾用 … 表示
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+// 5分钟涨跌幅最大的池子及其变化率(%)，未请求的一侧为 None
+#[derive(Debug, Default)]
+pub struct TopMovers<'a> {
+    pub gainers: Option<Vec<(&'a PoolInfo, f64)>>,
+    pub losers: Option<Vec<(&'a PoolInfo, f64)>>,
+}
+
+// 涨跌幅榜视图，代替按交易量排序的列表
+pub fn format_top_movers(
+    movers: &TopMovers,
+    timestamp: DateTime<Utc>,
+    price_alert: f64,
+    color: bool,
+) -> String {
+    let mut result = format!(
+        "🕒 Update time: {}\n\n",
+        timestamp.format("%Y-%m-%d %H:%M:%S")
+    );
+    for (title, pools) in [
+        ("🚀 Top gainers (5m)", &movers.gainers),
+        ("📉 Top losers (5m)", &movers.losers),
+    ] {
+        let Some(pools) = pools else {
+            continue;
+        };
+        result.push_str(&format!("{}\n", title));
+        if pools.is_empty() {
+            result.push_str("  none\n");
+        }
+        for (rank, (pool_info, change)) in pools.iter().enumerate() {
+            result.push_str(&format!(
+                "{:>3}. {:<width$} {:>16} {} {:>12}\n",
+                rank + 1,
+                truncate(&pool_info.pair_name(), TABLE_PAIR_WIDTH),
+                format_price(pool_info.price),
+                format_change(Some(*change), price_alert, color, 9),
+                humanize_usd(pool_info.volume_24h),
+                width = TABLE_PAIR_WIDTH
+            ));
+        }
+        result.push('\n');
+    }
+    result
+}
+
+// 显示选项
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayOptions {
+    // 显示前N个池子，0 表示全部
+    pub top_n: usize,
+    // 是否输出 ANSI 颜色
+    pub color: bool,
+    pub layout: Layout,
+    // 估算价格冲击的交易规模(USD)，0 表示不显示
+    pub impact_size: f64,
+}
+
+// 显示布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // 每个池子多行显示（默认）
+    Detailed,
+    // 每个池子一行的紧凑表格
+    Table,
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "detailed" => Ok(Layout::Detailed),
+            "table" => Ok(Layout::Table),
+            other => Err(anyhow::anyhow!(
+                "Unknown layout '{}', expected detailed or table",
+                other
+            )),
+        }
+    }
+}
+
+// 人类可读输出的目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputTarget {
+    Stdout,
+    File(PathBuf),
+    // 同时输出到 stdout 和文件
+    Both(PathBuf),
+}
+
+impl OutputTarget {
+    pub fn writes_stdout(&self) -> bool {
+        !matches!(self, OutputTarget::File(_))
+    }
+}
+
+impl FromStr for OutputTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s.eq_ignore_ascii_case("stdout") => Ok(OutputTarget::Stdout),
+            Some((kind, path)) if !path.is_empty() => match kind.to_lowercase().as_str() {
+                "file" => Ok(OutputTarget::File(PathBuf::from(path))),
+                "both" => Ok(OutputTarget::Both(PathBuf::from(path))),
+                other => Err(anyhow::anyhow!(
+                    "Unknown output '{}', expected stdout, file:PATH or both:PATH",
+                    other
+                )),
+            },
+            _ => Err(anyhow::anyhow!(
+                "Unknown output '{}', expected stdout, file:PATH or both:PATH",
+                s
+            )),
+        }
+    }
+}
+
+// 按 OutputTarget 输出文本，写入文件后立即 flush 便于 tail -f
+pub struct OutputSink {
+    stdout: bool,
+    file: Option<std::fs::File>,
+}
+
+impl OutputSink {
+    // truncate 为 true 时启动时清空文件，否则追加
+    pub fn open(target: &OutputTarget, truncate: bool) -> Result<Self> {
+        let path = match target {
+            OutputTarget::Stdout => None,
+            OutputTarget::File(path) | OutputTarget::Both(path) => Some(path),
+        };
+        let file = path
+            .map(|path| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(!truncate)
+                    .truncate(truncate)
+                    .open(path)
+                    .with_context(|| format!("Failed to open output file {}", path.display()))
+            })
+            .transpose()?;
+
+        Ok(OutputSink {
+            stdout: target.writes_stdout(),
+            file,
+        })
+    }
+
+    // 与 println! 一样在末尾追加换行
+    pub fn line(&mut self, text: &str) {
+        use std::io::Write;
+
+        if self.stdout {
+            println!("{}", text);
+        }
+        if let Some(file) = self.file.as_mut() {
+            if let Err(e) = writeln!(file, "{}", text).and_then(|_| file.flush()) {
+                tracing::error!("Failed to write output file: {}", e);
+            }
+        }
+    }
+}
+
+// 池子排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    // 按24小时交易量（默认）
+    Volume,
+    // 按动量分数
+    Momentum,
+}
+
+impl FromStr for SortBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "volume" => Ok(SortBy::Volume),
+            "momentum" => Ok(SortBy::Momentum),
+            other => Err(anyhow::anyhow!(
+                "Unknown sort field '{}', expected volume or momentum",
+                other
+            )),
+        }
+    }
+}
+
+// 市值计算方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketCapMode {
+    // 通过 RPC 查询代币供应量（准确，但受 RPC 限流影响）
+    Rpc,
+    // 使用池子储备估算（无网络请求，精度较低）
+    Fdv,
+}
+
+impl FromStr for MarketCapMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "rpc" => Ok(MarketCapMode::Rpc),
+            "fdv" => Ok(MarketCapMode::Fdv),
+            other => Err(anyhow::anyhow!(
+                "Unknown market cap mode '{}', expected rpc or fdv",
+                other
+            )),
+        }
+    }
+}
+
+// 颜色模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    // auto 模式下只有 stdout 是终端时才输出颜色
+    pub fn enabled(self) -> bool {
+        self.enabled_for(&OutputTarget::Stdout, std::env::var_os)
+    }
+
+    // 输出包含文件时 auto 模式不输出颜色，避免日志文件中出现 ANSI 转义码；
+    // 按 NO_COLOR 约定 (https://no-color.org)，设置了该环境变量时任何模式都不输出颜色。
+    // env 用于读取环境变量，通常传入 std::env::var_os，测试时可以注入
+    pub fn enabled_for(
+        self,
+        target: &OutputTarget,
+        env: impl Fn(&'static str) -> Option<std::ffi::OsString>,
+    ) -> bool {
+        if env("NO_COLOR").is_some() {
+            return false;
+        }
+        match self {
+            ColorMode::Auto => *target == OutputTarget::Stdout && std::io::stdout().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            other => Err(anyhow::anyhow!(
+                "Unknown color mode '{}', expected auto, always or never",
+                other
+            )),
+        }
+    }
+}
+
+// 输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(anyhow::anyhow!(
+                "Unknown output format '{}', expected text or json",
+                other
+            )),
+        }
+    }
+}
+
+// 单次快照的文本格式，没有历史数据，变化率显示为 n/a
+pub fn format_snapshot_text(pool_data: &PoolDataResult, top_n: usize) -> String {
+    let mut result = String::new();
+    result.push_str(&format!(
+        "🕒 Update time: {}\n\n",
+        pool_data.timestamp.format("%Y-%m-%d %H:%M:%S")
+    ));
+
+    for pool_info in pool_data.pools.iter().take(display_limit(top_n)) {
+        result.push_str(&format!(
+            "🔄 {} ({})\n\
+             💰 {}\n\
+             📈 Price: 5m:n/a | 15m:n/a | 1h:n/a | 24h:n/a\n\
+             📊 Vol: {} | 7d: {} | 30d: {}\n\
+             🏦 TVL: {}\n\
+             🌾 APR: {:.2}% | Fee APR: {:.2}%\n",
+            pool_info.id,
+            pool_info.pair_name(),
+            pool_info.price_label(),
+            humanize_usd(pool_info.volume_24h),
+            format_optional_usd(pool_info.volume_7d),
+            format_optional_usd(pool_info.volume_30d),
+            humanize_usd(pool_info.tvl),
+            pool_info.apr_24h,
+            pool_info.fee_apr_24h,
+        ));
+        result.push_str(&format_price_range(pool_info));
+        result.push_str("----------------------\n");
+    }
+
+    result
+}
+
+// 单次快照的 JSON 格式
+pub fn format_snapshot_json(pool_data: &PoolDataResult, top_n: usize) -> Result<String> {
+    let snapshot = PoolDataResult {
+        pools: pool_data
+            .pools
+            .iter()
+            .take(display_limit(top_n))
+            .cloned()
+            .collect(),
+        timestamp: pool_data.timestamp,
+        complete: pool_data.complete,
+    };
+
+    Ok(serde_json::to_string_pretty(&snapshot)?)
+}
+
+// 每次轮询的完整快照追加到 JSON lines 文件，按日期轮转：
+// snapshots.jsonl -> snapshots-2024-01-01.jsonl
+pub struct SnapshotArchive {
+    base: PathBuf,
+    current: Option<(chrono::NaiveDate, std::io::BufWriter<std::fs::File>)>,
+}
+
+impl SnapshotArchive {
+    pub fn new(base: &Path) -> Self {
+        SnapshotArchive {
+            base: base.to_path_buf(),
+            current: None,
+        }
+    }
+
+    fn path_for(&self, date: chrono::NaiveDate) -> PathBuf {
+        let stem = self
+            .base
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "snapshots".to_string());
+        let file_name = match self.base.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, date, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, date),
+        };
+        self.base.with_file_name(file_name)
+    }
+
+    // 追加一行并立即 flush，进程崩溃时最多丢失正在写的一条
+    pub fn append(&mut self, pool_data: &PoolDataResult) -> Result<()> {
+        use std::io::Write;
+
+        let date = pool_data.timestamp.date_naive();
+        if self.current.as_ref().map(|(d, _)| *d) != Some(date) {
+            let path = self.path_for(date);
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Failed to open snapshot archive {}", path.display()))?;
+            self.current = Some((date, std::io::BufWriter::new(file)));
+        }
+
+        if let Some((_, writer)) = self.current.as_mut() {
+            serde_json::to_writer(&mut *writer, pool_data)?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// 把每次请求的原始 API 响应保存到目录中，用于排查解析问题
+// 文件名带时间戳和标签（页码或批次），gzip 时追加 .gz 后缀
+#[derive(Debug, Clone)]
+pub struct RawDump {
+    pub dir: PathBuf,
+    pub gzip: bool,
+}
+
+impl RawDump {
+    pub fn new(dir: impl Into<PathBuf>, gzip: bool) -> Self {
+        RawDump {
+            dir: dir.into(),
+            gzip,
+        }
+    }
+
+    fn path_for(&self, time: DateTime<Utc>, label: &str) -> PathBuf {
+        let ext = if self.gzip { "json.gz" } else { "json" };
+        self.dir.join(format!(
+            "raydium-{}-{}.{}",
+            time.format("%Y%m%d-%H%M%S%.3f"),
+            label,
+            ext
+        ))
+    }
+
+    pub fn write(&self, time: DateTime<Utc>, label: &str, data: &Value) -> Result<PathBuf> {
+        use std::io::Write;
+
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create dump directory {}", self.dir.display()))?;
+        let path = self.path_for(time, label);
+        let file = std::fs::File::create(&path)
+            .with_context(|| format!("Failed to create raw dump {}", path.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        if self.gzip {
+            let mut encoder =
+                flate2::write::GzEncoder::new(&mut writer, flate2::Compression::default());
+            serde_json::to_writer(&mut encoder, data)?;
+            encoder.finish()?;
+        } else {
+            serde_json::to_writer(&mut writer, data)?;
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+
+    // 保存失败不影响监控，只记录警告
+    fn write_or_warn(&self, time: DateTime<Utc>, label: &str, data: &Value) {
+        match self.write(time, label, data) {
+            Ok(path) => tracing::debug!("Dumped raw response to {}", path.display()),
+            Err(e) => tracing::warn!("Failed to dump raw response: {:#}", e),
+        }
+    }
+}
+
+// 历史数据的时间序列表格
+pub fn format_history_table(pool_id: &str, records: &[HistoricalData]) -> String {
+    let mut result = String::new();
+    result.push_str(&format!(
+        "📜 History for {} ({} records)\n\n",
+        pool_id,
+        records.len()
+    ));
+    result.push_str(&format!(
+        "{:<20} {:>16} {:>16} {:>16}\n",
+        "Time", "Price", "Volume(24h)", "TVL"
+    ));
+
+    for record in records {
+        result.push_str(&format!(
+            "{:<20} {:>16} {:>16.2} {:>16.2}\n",
+            record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            format_price(record.price),
+            record.volume_24h,
+            record.tvl,
+        ));
+    }
+
+    result
+}
+
+#[test]
+fn test_first_poll_lists_pools_without_history() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+
+    rt.block_on(async {
+        let pool_data = parse_raydium_pools(&data, Utc::now()).unwrap();
+        let pool_monitor = PoolMonitor::new();
+        let thresholds = AlertThresholds::default();
+
+        let display = DisplayOptions {
+            top_n: 20,
+            color: false,
+            layout: Layout::Detailed,
+            impact_size: 0.0,
+        };
+
+        // 第一次轮询前没有任何历史数据
+        let (output, _) =
+            format_pool_data(&pool_data, &pool_monitor, &display, &thresholds, true).await;
+
+        assert!(output.contains("6QVQKPE5JeWTwsSumYJkJHPHoukW23D8XeRLzk7oAnqg (WSOL/$slop)"));
+        assert!(output.contains("📈 Price: 5m:n/a | 15m:n/a | 1h:n/a | 24h:n/a"));
+        assert!(output.contains("📊 Vol: $152M"));
+    });
+}
+
+#[test]
+fn test_record_lookup_matches_linear_scan() {
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    let start = Utc::now() - chrono::Duration::days(2);
+    let mut timestamp = start;
+    let mut history = PoolHistory::new();
+    for _ in 0..500 {
+        // 间隔不均匀，允许重复时间戳
+        timestamp += chrono::Duration::seconds(rng.gen_range(0..600));
+        history.push_back(HistoricalData {
+            volume_24h: rng.gen_range(0.0..1_000_000.0),
+            volume_7d: None,
+            price: rng.gen_range(0.0..100.0),
+            tvl: rng.gen_range(0.0..1_000_000.0),
+            apr: 0.0,
+            reserve_a: 0.0,
+            reserve_b: 0.0,
+            timestamp,
+        });
+    }
+
+    for _ in 0..1000 {
+        let target = start + chrono::Duration::seconds(rng.gen_range(-600..200_000));
+        let linear = history.iter().rev().find(|r| r.timestamp <= target);
+        let binary = PoolMonitor::record_at_or_before(&history, target);
+        assert_eq!(
+            linear.map(|r| r as *const HistoricalData),
+            binary.map(|r| r as *const HistoricalData)
+        );
+    }
+}
+
+#[test]
+fn test_unchanged_records_are_deduplicated() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+
+    rt.block_on(async {
+        let start = Utc::now() - chrono::Duration::hours(1);
+        let pool_monitor = PoolMonitor::new();
+        let mut pool_info = parse_raydium_pools(&data, start).unwrap().pools.remove(0);
+        async fn record_count(pool_monitor: &PoolMonitor) -> usize {
+            pool_monitor.historical_data.lock().await
+                ["6QVQKPE5JeWTwsSumYJkJHPHoukW23D8XeRLzk7oAnqg"]
+                .len()
+        }
+
+        pool_monitor.update_historical_data(&pool_info).await;
+
+        // 30秒后数据未变化，不新增记录
+        pool_info.timestamp = start + chrono::Duration::seconds(30);
+        pool_monitor.update_historical_data(&pool_info).await;
+        assert_eq!(record_count(&pool_monitor).await, 1);
+
+        // 价格变化时正常记录
+        pool_info.timestamp = start + chrono::Duration::seconds(60);
+        pool_info.price += 1.0;
+        pool_monitor.update_historical_data(&pool_info).await;
+        assert_eq!(record_count(&pool_monitor).await, 2);
+
+        // 超过最大间隔后即使数据未变化也会记录
+        pool_info.timestamp = start + chrono::Duration::seconds(60) + history_dedup_max_gap();
+        pool_monitor.update_historical_data(&pool_info).await;
+        assert_eq!(record_count(&pool_monitor).await, 3);
+    });
+}
+
+#[test]
+fn test_no_color_env_disables_ansi_output() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+
+    rt.block_on(async {
+        let now = Utc::now();
+        let pool_data = parse_raydium_pools(&data, now).unwrap();
+        let pool_monitor = PoolMonitor::new();
+        let pool_info = &pool_data.pools[0];
+        // 10分钟前价格低 10%，使变化率超过阈值并着色
+        pool_monitor.historical_data.lock().await.insert(
+            pool_info.id.clone(),
+            PoolHistory::from(vec![HistoricalData {
+                volume_24h: pool_info.volume_24h,
+                volume_7d: None,
+                price: pool_info.price * 0.9,
+                tvl: pool_info.tvl,
+                apr: 0.0,
+                reserve_a: 0.0,
+                reserve_b: 0.0,
+                timestamp: now - chrono::Duration::minutes(10),
+            }]),
+        );
+        pool_monitor.update_historical_data(pool_info).await;
+        let thresholds = AlertThresholds::default();
+        let format = |color: bool| {
+            let display = DisplayOptions {
+                top_n: 20,
+                color,
+                layout: Layout::Detailed,
+                impact_size: 0.0,
+            };
+            let pool_monitor = &pool_monitor;
+            let pool_data = &pool_data;
+            async move {
+                format_pool_data(pool_data, pool_monitor, &display, &thresholds, true)
+                    .await
+                    .0
+            }
+        };
+
+        // 注入环境变量，不修改进程环境（测试并行运行）
+        let color = ColorMode::Always.enabled_for(&OutputTarget::Stdout, |_| None);
+        assert!(format(color).await.contains('\x1b'));
+
+        let no_color = |key: &str| (key == "NO_COLOR").then(|| "1".into());
+        let color = ColorMode::Always.enabled_for(&OutputTarget::Stdout, no_color);
+        assert!(!color);
+        assert!(!format(color).await.contains('\x1b'));
+    });
+}
+
+#[test]
+fn test_replay_threshold_crossing_alerts_once() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    // 只保留夹具中的第一个池子并替换价格
+    let snapshot = |price: f64, minutes_ago: i64| {
+        let mut pool_data =
+            parse_raydium_pools(&data, Utc::now() - chrono::Duration::minutes(minutes_ago))
+                .unwrap();
+        pool_data.pools.truncate(1);
+        pool_data.pools[0].price = price;
+        pool_data
+    };
+    // 第2个快照价格上涨 10%，第3个快照保持在阈值之外（处于冷却期）
+    let source = ReplaySource::new([snapshot(100.0, 10), snapshot(110.0, 2), snapshot(110.5, 1)]);
+
+    rt.block_on(async {
+        let pool_monitor = PoolMonitor::new();
+        let display = DisplayOptions {
+            top_n: 20,
+            color: false,
+            layout: Layout::Detailed,
+            impact_size: 0.0,
+        };
+        let thresholds = AlertThresholds::default();
+
+        let mut alerts_per_poll = Vec::new();
+        while source.remaining() > 0 {
+            let pool_data = source.fetch().await.unwrap();
+            for pool_info in &pool_data.pools {
+                pool_monitor.update_historical_data(pool_info).await;
+            }
+            let (_, alerts) =
+                format_pool_data(&pool_data, &pool_monitor, &display, &thresholds, true).await;
+            alerts_per_poll.push(alerts);
+        }
+
+        assert!(alerts_per_poll[0].is_empty());
+        assert_eq!(alerts_per_poll[1].len(), 1);
+        assert_eq!(alerts_per_poll[1][0].metric, AlertKind::Price);
+        // 5分钟前的参考价格在 -10m(100) 和 -2m(110) 两个快照之间插值为 103.75
+        assert!((alerts_per_poll[1][0].value - (110.0 / 103.75 - 1.0) * 100.0).abs() < 1e-4);
+        assert!(alerts_per_poll[2].is_empty());
+        assert!(source.fetch().await.is_err());
+    });
+}
+
+#[test]
+fn test_disabled_alerts_keep_cooldown_free() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    rt.block_on(async {
+        let now = Utc::now();
+        let pool_monitor = PoolMonitor::new();
+        let mut pool_data = parse_raydium_pools(&data, now).unwrap();
+        pool_data.pools.truncate(1);
+        let mut before = pool_data.pools[0].clone();
+        before.price *= 0.9;
+        before.timestamp = now - chrono::Duration::minutes(10);
+        pool_monitor.update_historical_data(&before).await;
+        pool_monitor
+            .update_historical_data(&pool_data.pools[0])
+            .await;
+        let display = DisplayOptions {
+            top_n: 20,
+            color: false,
+            layout: Layout::Detailed,
+            impact_size: 0.0,
+        };
+        let thresholds = AlertThresholds::default();
+
+        // 异常的一轮不产生警报，也不进入冷却期
+        let (_, alerts) =
+            format_pool_data(&pool_data, &pool_monitor, &display, &thresholds, false).await;
+        assert!(alerts.is_empty());
+        let (_, alerts) =
+            format_pool_data(&pool_data, &pool_monitor, &display, &thresholds, true).await;
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].metric, AlertKind::Price);
+    });
+}
+
+#[test]
+fn test_detect_anomaly_rules() {
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    let pool_data = parse_raydium_pools(&data, Utc::now()).unwrap();
+    let total = pool_data.pools.len();
+    let thresholds = AnomalyThresholds {
+        max_ratio: 50.0,
+        min_pools: total,
+    };
+    assert_eq!(pool_data.detect_anomaly(&thresholds), None);
+
+    // 价格为0和 TVL 为负都算无效
+    let mut invalid = pool_data.clone();
+    invalid.pools[0].price = 0.0;
+    invalid.pools[1].tvl = -1.0;
+    let reason = invalid.detect_anomaly(&thresholds).unwrap();
+    assert_eq!(
+        reason,
+        format!(
+            "2 of {} pools have a zero/invalid price or negative TVL",
+            total
+        )
+    );
+    // 占比没有超过阈值时不算异常
+    invalid.pools[1].tvl = 1.0;
+    assert_eq!(invalid.detect_anomaly(&thresholds), None);
+
+    // 过多池子报告完全相同的价格
+    let mut identical = pool_data.clone();
+    for pool_info in &mut identical.pools {
+        pool_info.price = 1.5;
+    }
+    let reason = identical.detect_anomaly(&thresholds).unwrap();
+    assert_eq!(
+        reason,
+        format!(
+            "{} of {} pools report the identical price 1.5",
+            total, total
+        )
+    );
+
+    // 池子数少于 min_pools 或关闭检查时不判定
+    let thresholds = AnomalyThresholds {
+        max_ratio: 50.0,
+        min_pools: total + 1,
+    };
+    assert_eq!(identical.detect_anomaly(&thresholds), None);
+    let thresholds = AnomalyThresholds {
+        max_ratio: 0.0,
+        min_pools: 0,
+    };
+    assert_eq!(identical.detect_anomaly(&thresholds), None);
+}
+
+#[test]
+fn test_aggregated_alerts_notify_one_summary() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    rt.block_on(async {
+        let now = Utc::now();
+        let pool_monitor = PoolMonitor::new();
+        let pool_data = parse_raydium_pools(&data, now).unwrap();
+        // 10分钟前所有池子的价格都低 10%
+        for mut pool_info in parse_raydium_pools(&data, now - chrono::Duration::minutes(10))
+            .unwrap()
+            .pools
+        {
+            pool_info.price *= 0.9;
+            pool_monitor.update_historical_data(&pool_info).await;
+        }
+        for pool_info in &pool_data.pools {
+            pool_monitor.update_historical_data(pool_info).await;
+        }
+        let display = DisplayOptions {
+            top_n: 20,
+            color: false,
+            layout: Layout::Detailed,
+            impact_size: 0.0,
+        };
+        let thresholds = AlertThresholds {
+            alert_aggregate: 2,
+            ..AlertThresholds::default()
+        };
+
+        let (output, alerts) =
+            format_pool_data(&pool_data, &pool_monitor, &display, &thresholds, true).await;
+
+        // 通知渠道只收到一条汇总，而不是每个池子一条
+        assert!(pool_data.pools.len() > 2);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].metric, AlertKind::Price);
+        assert_eq!(alerts[0].value, pool_data.pools.len() as f64);
+        let summary = alerts[0].summary.as_deref().unwrap();
+        assert!(summary.starts_with(&format!("📈 {} pools moved", pool_data.pools.len())));
+        assert_eq!(alerts[0].to_string(), summary);
+        assert!(output.contains(summary));
+    });
+}
+
+#[test]
+fn test_event_alerts_describe_listing_and_crossing() {
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    let pool_data = parse_raydium_pools(&data, Utc::now()).unwrap();
+    let pool_info = &pool_data.pools[0];
+
+    let alerts = new_pool_alerts(&[pool_info]);
+    assert_eq!(alerts[0].metric, AlertKind::NewPool);
+    assert!(alerts[0]
+        .to_string()
+        .starts_with("[WSOL/$slop] new pool listed at price"));
+
+    let alerts = price_crossing_alerts(&[PriceCrossing {
+        pool_id: pool_info.id.clone(),
+        symbol_a: pool_info.symbol_a.clone(),
+        symbol_b: pool_info.symbol_b.clone(),
+        level: 100.0,
+        price: 90.0,
+        upward: false,
+    }]);
+    assert!(alerts[0]
+        .to_string()
+        .starts_with("[WSOL/$slop] price fell below target 100"));
+
+    let alerts = disappeared_pool_alerts(std::slice::from_ref(&pool_info.id));
+    assert_eq!(
+        alerts[0].to_string(),
+        format!("pool {} no longer listed", pool_info.id)
+    );
+}
+
+#[test]
+fn test_ingest_csv_counts_retained_records() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let now = Utc::now();
+    let path = std::env::temp_dir().join(format!("ingest-{}.csv", std::process::id()));
+    let row = |timestamp: DateTime<Utc>, price: f64| {
+        format!("POOL,{},{},1000,500\n", timestamp.to_rfc3339(), price)
+    };
+    // 第二行与第一行相同会被去重，最后一行超出保留期
+    let content = [
+        "pool_id,timestamp,price,volume_24h,tvl\n".to_string(),
+        row(now - chrono::Duration::minutes(10), 1.0),
+        row(now - chrono::Duration::minutes(9), 1.0),
+        row(now - chrono::Duration::minutes(8), 2.0),
+        row(now - chrono::Duration::days(365), 3.0),
+    ]
+    .concat();
+    std::fs::write(&path, content).unwrap();
+
+    rt.block_on(async {
+        let pool_monitor = PoolMonitor::new();
+        let retained = pool_monitor.ingest_csv(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(retained, 2);
+        assert_eq!(pool_monitor.historical_data.lock().await["POOL"].len(), 2);
+    });
+}
+
+#[test]
+fn test_disappeared_pools_in_complete_snapshot() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    rt.block_on(async {
+        let pool_monitor = PoolMonitor::new();
+        let mut previous = parse_raydium_pools(&data, Utc::now()).unwrap();
+        previous.complete = true;
+        pool_monitor.record_snapshot(&previous).await;
+
+        let mut current = previous.clone();
+        let removed = current.pools.remove(1);
+        assert_eq!(pool_monitor.disappeared_pools(&current).await, [removed.id]);
+
+        // 不完整的快照中消失的池子可能只是排到了后面的页
+        current.complete = false;
+        assert!(pool_monitor.disappeared_pools(&current).await.is_empty());
+    });
+}
+
+#[test]
+fn test_parse_list_response_fixture() {
+    // 列表接口的完整响应：正常池子、缺少 day.volume、symbol 为 null、WSOL/USDC
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    let now = Utc::now();
+    let mut pool_data = parse_raydium_pools(&data, now).unwrap();
+    // 解析保持接口顺序，由调用方按交易量排序
+    sort_top_n_by_volume(&mut pool_data.pools, 0);
+
+    // WSOL/USDC 被过滤，其余按24小时交易量降序
+    assert!(!pool_data.complete);
+    let ids: Vec<&str> = pool_data.pools.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(
+        ids,
+        [
+            "6QVQKPE5JeWTwsSumYJkJHPHoukW23D8XeRLzk7oAnqg",
+            "9fmdkQipJK2teeUv53BMDXi52uRLbrEvV38K8GBNNiM7",
+            "3ucNos4NbumPLZNWztqGHNFFgkHeRMBQAVemeeomsUxv",
+        ]
+    );
+
+    let standard = &pool_data.pools[0];
+    assert_eq!(standard.symbol_a, "WSOL");
+    assert_eq!(standard.symbol_b, "$slop");
+    assert_eq!(
+        standard.symbol_b_address,
+        "FqvtZ2UFR9we82Ni4LeacC1zyTiQ77usDo31DUokpump"
+    );
+    assert_eq!(standard.symbol_b_decimals, 6);
+    assert_eq!(standard.type_tag(), "AMM");
+    assert_eq!(standard.pool_tags, ["OpenBookMarket"]);
+    assert_eq!(standard.volume_24h, 152266185.89469922);
+    assert_eq!(standard.volume_7d, Some(179906535.6488955));
+    assert_eq!(standard.tvl, 1171602.1);
+    assert_eq!(standard.price, 6948.933948075416);
+    assert_eq!(standard.price_a_in_b, 6948.933948075416);
+    assert_eq!(standard.price_b_in_a, 1.0 / 6948.933948075416);
+    assert_eq!(
+        standard.price_label(),
+        "1 WSOL = 6,948.93 $slop | 1 $slop = 0.0001439 WSOL"
+    );
+    assert_eq!(standard.price_min_24h, 3044.857707702913);
+    assert_eq!(standard.price_max_24h, 245156.5042684039);
+    assert_eq!(standard.apr_24h, 11859.22);
+    assert_eq!(standard.fee_rate, 0.0025);
+    assert_eq!(standard.reserve_a, 3535.924424012);
+    assert_eq!(standard.reserve_b, 24570905.267846);
+    assert_eq!(standard.burn_percent, 80.45);
+    assert_eq!(
+        standard.lp_mint,
+        "HThpmCrwsn7bueJaFa5ScrU9HtA9ToaMVU1rSccXUsjG"
+    );
+    assert_eq!(standard.open_time, DateTime::from_timestamp(1728000000, 0));
+    assert!(standard.fdv_estimate.is_some());
+    assert_eq!(standard.market_cap, None);
+    assert_eq!(standard.timestamp, now);
+
+    // symbol 为 null 时使用 mint 地址
+    let unnamed = &pool_data.pools[1];
+    assert_eq!(
+        unnamed.symbol_a,
+        "So11111111111111111111111111111111111111112"
+    );
+    assert_eq!(
+        unnamed.symbol_b,
+        "7GCihgDB8fe6KNjn2MYtkzZcRjQy3t9GHdC8uHYmW2hr"
+    );
+    assert_eq!(unnamed.volume_24h, 1000.5);
+
+    // 缺少 day.volume 时按 0 处理，openTime 为 0 表示未知
+    let no_volume = &pool_data.pools[2];
+    assert_eq!(no_volume.volume_24h, 0.0);
+    assert_eq!(no_volume.symbol_b, "BONK2");
+    assert_eq!(no_volume.type_tag(), "CLMM");
+    assert!(no_volume.pool_tags.is_empty());
+    assert_eq!(no_volume.open_time, None);
+    assert_eq!(no_volume.apr_24h, 11859.22);
+}
+
+#[test]
+fn test_format_price_tiny_normal_and_huge() {
+    // 极小价格保留有效数字
+    assert_eq!(format_price(0.00000012), "0.00000012");
+    assert_eq!(format_price(0.000001234567), "0.000001235");
+    assert_eq!(format_price(0.0123456), "0.01235");
+    assert_eq!(format_price(3.2e-12), "3.200e-12");
+
+    // 普通价格
+    assert_eq!(format_price(1.0), "1");
+    assert_eq!(format_price(1.23456), "1.235");
+    assert_eq!(format_price(150.27), "150.3");
+    assert_eq!(format_price(0.0), "0");
+
+    // 大价格加千位分隔符，四舍五入到1000时也按大价格显示
+    assert_eq!(format_price(6948.933948075416), "6,948.93");
+    assert_eq!(format_price(999.996), "1,000.00");
+    assert_eq!(format_price(1234567890.126), "1,234,567,890.13");
+    assert_eq!(format_price(-2500.5), "-2,500.50");
+    assert_eq!(format_price(f64::NAN), "n/a");
+}
+
+#[test]
+fn test_humanize_usd_unit_boundaries() {
+    assert_eq!(humanize_usd(0.0), "$0.00");
+    assert_eq!(humanize_usd(999.99), "$999.99");
+    assert_eq!(humanize_usd(1_000.0), "$1.00K");
+    assert_eq!(humanize_usd(50_000.0), "$50.0K");
+    assert_eq!(humanize_usd(123_456.0), "$123K");
+    // 四舍五入到 1000K 时进位为 M
+    assert_eq!(humanize_usd(999_999.0), "$1.00M");
+    assert_eq!(humanize_usd(1_000_000.0), "$1.00M");
+    assert_eq!(humanize_usd(152_266_185.89), "$152M");
+    assert_eq!(humanize_usd(999_999_999.0), "$1.00B");
+    assert_eq!(humanize_usd(2_300_000_000.0), "$2.30B");
+    // 没有更大的单位时继续使用 B
+    assert_eq!(humanize_usd(1_234_000_000_000.0), "$1234B");
+    assert_eq!(humanize_usd(-50_000.0), "-$50.0K");
+}
+
+#[test]
+fn test_changes_interpolate_between_bracketing_records() {
+    let record = |price: f64, minutes_ago: u64| HistoricalData {
+        volume_24h: 1000.0,
+        volume_7d: None,
+        price,
+        tvl: 500.0,
+        apr: 0.0,
+        reserve_a: 0.0,
+        reserve_b: 0.0,
+        timestamp: 100 * 60_000 - minutes_ago * 60_000,
+    };
+
+    // 轮询中断：5分钟前的参考点落在 -20m 和 -2m 两条记录之间
+    let history: VecDeque<HistoricalData<u64>> =
+        [record(100.0, 20), record(118.0, 2), record(120.0, 0)].into();
+    let changes = PoolMonitor::changes_in(&history).unwrap();
+    // 插值得到 -5m 时价格为 115
+    let expected = PoolMonitor::calculate_change(115.0, 120.0);
+    assert!((changes.price_change_5m.unwrap() - expected).abs() < 1e-9);
+    assert_eq!(changes.reference_age_5m, Some(Duration::from_secs(20 * 60)));
+    // 15分钟窗口的参考记录只早5分钟，不提示
+    assert_eq!(
+        changes.stale_windows(),
+        vec![("5m", Duration::from_secs(20 * 60))]
+    );
+    // 1小时前没有记录
+    assert_eq!(changes.price_change_1h, None);
+
+    // 正好有5分钟前的记录时不插值，也不提示
+    let history: VecDeque<HistoricalData<u64>> = [record(100.0, 5), record(110.0, 0)].into();
+    let changes = PoolMonitor::changes_in(&history).unwrap();
+    assert!((changes.price_change_5m.unwrap() - 10.0).abs() < 1e-9);
+    assert!(changes.stale_windows().is_empty());
+}
+
+#[test]
+fn test_memory_report_counts_records_per_pool() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let pool_monitor = PoolMonitor::new();
+        assert_eq!(pool_monitor.memory_report().await, HistoryStats::default());
+
+        let start = Utc::now() - chrono::Duration::hours(1);
+        let record = |minutes: i64| HistoricalData {
+            volume_24h: 1000.0,
+            volume_7d: None,
+            price: 1.0,
+            tvl: 500.0,
+            apr: 0.0,
+            reserve_a: 0.0,
+            reserve_b: 0.0,
+            timestamp: start + chrono::Duration::minutes(minutes),
+        };
+        {
+            let mut historical_data = pool_monitor.historical_data.lock().await;
+            historical_data.insert("a".to_string(), [record(0), record(1), record(2)].into());
+            historical_data.insert("b".to_string(), [record(5)].into());
+        }
+
+        let stats = pool_monitor.memory_report().await;
+        assert_eq!(stats.pools, 2);
+        assert_eq!(stats.total_records, 4);
+        assert_eq!(stats.min_records_per_pool, 1);
+        assert_eq!(stats.max_records_per_pool, 3);
+        assert_eq!(stats.avg_records_per_pool, 2.0);
+        assert_eq!(stats.oldest, Some(start));
+        assert_eq!(stats.newest, Some(start + chrono::Duration::minutes(5)));
+    });
+}
+
+#[test]
+fn test_top_movers_split_gainers_and_losers() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    rt.block_on(async {
+        let pool_monitor = PoolMonitor::new();
+        let now = Utc::now();
+        let before = parse_raydium_pools(&data, now - chrono::Duration::minutes(5)).unwrap();
+        let mut after = parse_raydium_pools(&data, now).unwrap();
+        // 依次 +10%、-5%、+2%，最后一个不变
+        for (pool_info, factor) in after.pools.iter_mut().zip([1.10, 0.95, 1.02]) {
+            pool_info.price *= factor;
+        }
+        for pool_info in before.pools.iter().chain(&after.pools) {
+            pool_monitor.update_historical_data(pool_info).await;
+        }
+
+        let movers = pool_monitor.top_movers(&after.pools, 5, 5, 0.0).await;
+        let gainers = movers.gainers.unwrap();
+        assert_eq!(gainers.len(), 2);
+        assert_eq!(gainers[0].0.id, after.pools[0].id);
+        assert!((gainers[0].1 - 10.0).abs() < 1e-6);
+        let losers = movers.losers.unwrap();
+        assert_eq!(losers.len(), 1);
+        assert_eq!(losers[0].0.id, after.pools[1].id);
+
+        // 交易量过滤，未请求的一侧为 None
+        let movers = pool_monitor
+            .top_movers(&after.pools, 1, 0, f64::INFINITY)
+            .await;
+        assert_eq!(movers.gainers.map(|gainers| gainers.len()), Some(0));
+        assert!(movers.losers.is_none());
+    });
+}
+
+#[test]
+fn test_retain_pools_reselects_top_n_after_filtering() {
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    let mut pool_data = parse_raydium_pools(&data, Utc::now()).unwrap();
+    for (pool_info, volume) in pool_data.pools.iter_mut().zip([400.0, 100.0, 300.0]) {
+        pool_info.volume_24h = volume;
+    }
+    // 抓取时只保证第一个有序
+    sort_top_n_by_volume(&mut pool_data.pools, 1);
+    assert_eq!(pool_data.pools[0].volume_24h, 400.0);
+
+    // 过滤掉前缀中的池子后，第一个仍是剩余池子中交易量最高的，而不是未排序部分的 100
+    pool_data.retain_pools(1, |pool_info| pool_info.volume_24h < 400.0);
+    let volumes: Vec<_> = pool_data
+        .pools
+        .iter()
+        .map(|pool_info| pool_info.volume_24h)
+        .collect();
+    assert_eq!(volumes, [300.0, 100.0]);
+}
+
+#[test]
+fn test_momentum_score_keeps_price_direction() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    rt.block_on(async {
+        let pool_monitor = PoolMonitor::new();
+        let now = Utc::now();
+        let before = parse_raydium_pools(&data, now - chrono::Duration::minutes(5)).unwrap();
+        let mut after = parse_raydium_pools(&data, now).unwrap();
+        // 上涨 30%、下跌 30%、价格和交易量都暴涨
+        for (pool_info, factor) in after.pools.iter_mut().zip([1.3, 0.7, 1000.0]) {
+            pool_info.price *= factor;
+        }
+        after.pools[2].volume_24h *= 1000.0;
+        for pool_info in before.pools.iter().chain(&after.pools) {
+            pool_monitor.update_historical_data(pool_info).await;
+        }
+
+        // 分数与公式一致
+        let expected = |changes: ChangeMetrics| {
+            let term = |change: Option<f64>, scale: f64| (change.unwrap_or(0.0) / scale).tanh();
+            100.0
+                * (0.4 * term(changes.price_change_5m, 2.0)
+                    + 0.3 * term(changes.price_change_1h, 5.0)
+                    + 0.3 * term(changes.volume_change_1h, 50.0))
+        };
+        let mut scores = Vec::new();
+        for pool_info in &after.pools[..3] {
+            let score = pool_monitor.momentum_score(&pool_info.id).await.unwrap();
+            let changes = pool_monitor.get_changes(&pool_info.id).await.unwrap();
+            assert!((score - expected(changes)).abs() < 1e-9);
+            scores.push(score);
+        }
+
+        // 上涨为正，暴跌为负
+        assert!(scores[0] > 0.0);
+        assert!(scores[1] < 0.0);
+        // 极端变化被 tanh 压在 [-100, 100] 内
+        assert!(scores[2] > scores[0] && scores[2] <= 100.0);
+
+        // 暴跌的池子排在有分数的池子最后
+        let mut sorted = after.pools[..3].to_vec();
+        pool_monitor.sort_by_momentum(&mut sorted).await;
+        let order: Vec<_> = sorted.iter().map(|pool_info| &pool_info.id).collect();
+        assert_eq!(
+            order,
+            [&after.pools[2].id, &after.pools[0].id, &after.pools[1].id]
+        );
+    });
+}
+
+#[test]
+fn test_price_impact_constant_product() {
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    let mut pool_info = parse_raydium_pools(&data, Utc::now())
+        .unwrap()
+        .pools
+        .remove(0);
+    pool_info.tvl = 2_000_000.0;
+    pool_info.reserve_a = 1000.0;
+    pool_info.reserve_b = 1000.0;
+    pool_info.price = 1.0;
+
+    // 每侧 $1M，买入 $1000 的冲击为 1000 / 1001000
+    assert!((pool_info.price_impact(1000.0) - 1000.0 / 1_001_000.0 * 100.0).abs() < 1e-12);
+    // 规模越大冲击越大
+    assert!(pool_info.price_impact(100_000.0) > pool_info.price_impact(1000.0));
+    assert_eq!(pool_info.price_impact(0.0), 0.0);
+
+    // 两侧不平衡时按被买入一侧（WSOL 之外的代币 B）的价值计算：
+    // A 侧价值 3000、B 侧 1000，B 侧只占 TVL 的 1/4
+    pool_info.price = 3.0;
+    assert!((pool_info.price_impact(1000.0) - 1000.0 / 501_000.0 * 100.0).abs() < 1e-12);
+
+    // 没有流动性的池子无法交易
+    pool_info.reserve_b = 0.0;
+    assert_eq!(pool_info.price_impact(1000.0), 100.0);
+}
+
+#[test]
+fn test_detailed_view_marks_estimated_impact() {
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    let pool_data = parse_raydium_pools(&data, Utc::now()).unwrap();
+    let display = DisplayOptions {
+        top_n: 20,
+        color: false,
+        layout: Layout::Detailed,
+        impact_size: 1000.0,
+    };
+    let thresholds = AlertThresholds::default();
+    let detailed =
+        |pool_info: &PoolInfo| format_pool_detailed(pool_info, None, None, &display, &thresholds);
+    let marker = " (est., constant-product approximation)";
+
+    let standard = &pool_data.pools[0];
+    assert!(standard.is_constant_product());
+    let output = detailed(standard);
+    assert!(output.contains("💧 Impact($1.00K): "));
+    assert!(!output.contains(marker));
+
+    // CLMM 池子的冲击只是估算，需要标注
+    let concentrated = &pool_data.pools[1];
+    assert_eq!(concentrated.pool_type, "Concentrated");
+    assert!(detailed(concentrated).contains(marker));
+}
+
+#[test]
+fn test_supply_in_pool_pct_uses_token_side_reserve() {
+    let data: Value =
+        serde_json::from_str(include_str!("../tests/fixtures/pool_list.json")).unwrap();
+    let mut pool_info = parse_raydium_pools(&data, Utc::now())
+        .unwrap()
+        .pools
+        .remove(0);
+    pool_info.reserve_a = 500.0;
+    pool_info.reserve_b = 250_000.0;
+
+    // 还没有查询过供应量
+    assert_eq!(pool_info.supply_in_pool_pct(), None);
+
+    // mintA 是 WSOL，代币在 mintB 一侧
+    pool_info.token_supply = Some(1_000_000.0);
+    assert!((pool_info.supply_in_pool_pct().unwrap() - 25.0).abs() < 1e-9);
+
+    // 两侧都不是报价资产时无法判断
+    pool_info.symbol_a_address = "FqvtZ2UFR9we82Ni4LeacC1zyTiQ77usDo31DUokpump".to_string();
+    assert_eq!(pool_info.supply_in_pool_pct(), None);
+}
+
+#[test]
+fn test_price_correlation_of_aligned_histories() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let pool_monitor = PoolMonitor::new();
+        let start = Utc::now() - chrono::Duration::minutes(30);
+        let record = |price: f64, seconds: i64| HistoricalData {
+            volume_24h: 1000.0,
+            volume_7d: None,
+            price,
+            tvl: 500.0,
+            apr: 0.0,
+            reserve_a: 0.0,
+            reserve_b: 0.0,
+            timestamp: start + chrono::Duration::seconds(seconds),
+        };
+        {
+            let mut historical_data = pool_monitor.historical_data.lock().await;
+            // b 与 a 同涨同跌，采样时间错开几秒；c 与 a 反向
+            let prices = [1.0, 1.2, 1.1, 1.5, 1.4, 1.8, 1.6, 2.0, 1.9, 2.2, 2.1, 2.5];
+            let series = |f: &dyn Fn(f64) -> f64, offset: i64| -> PoolHistory {
+                prices
+                    .iter()
+                    .enumerate()
+                    .map(|(i, price)| record(f(*price), i as i64 * 60 + offset))
+                    .collect()
+            };
+            historical_data.insert("a".to_string(), series(&|p| p, 0));
+            historical_data.insert("b".to_string(), series(&|p| p * 3.0 + 1.0, 5));
+            historical_data.insert("c".to_string(), series(&|p| 10.0 - p, 0));
+            historical_data.insert(
+                "short".to_string(),
+                [record(1.0, 0), record(2.0, 60)].into(),
+            );
+        }
+
+        let window = chrono::Duration::hours(1);
+        let ab = pool_monitor.correlation("a", "b", window).await.unwrap();
+        assert!((ab - 1.0).abs() < 1e-9);
+        let ac = pool_monitor.correlation("a", "c", window).await.unwrap();
+        assert!((ac + 1.0).abs() < 1e-9);
+        // 重叠的历史太短
+        assert_eq!(pool_monitor.correlation("a", "short", window).await, None);
+        assert_eq!(pool_monitor.correlation("a", "missing", window).await, None);
+    });
 }
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+// 历史数据的时间戳。变化率等分析逻辑只通过这个 trait 获取当前时间和做时间加减，
+// 默认使用 chrono 的 DateTime<Utc>，嵌入时也可以直接用 u64 毫秒时间戳
+pub trait Timestamp: Copy + Ord {
+    fn now() -> Self;
+
+    // 往前回退 duration，溢出时取最小值
+    fn minus(self, duration: Duration) -> Self;
+
+    // 距 earlier 的时长，earlier 更晚时为 0
+    fn since(self, earlier: Self) -> Duration;
+}
+
+// 默认时间戳类型
+pub type DefaultTimestamp = DateTime<Utc>;
+
+impl Timestamp for DateTime<Utc> {
+    fn now() -> Self {
+        Utc::now()
+    }
+
+    fn minus(self, duration: Duration) -> Self {
+        chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| self.checked_sub_signed(duration))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+
+    fn since(self, earlier: Self) -> Duration {
+        (self - earlier).to_std().unwrap_or(Duration::ZERO)
+    }
+}
+
+// Unix 毫秒时间戳
+impl Timestamp for u64 {
+    fn now() -> Self {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    fn minus(self, duration: Duration) -> Self {
+        self.saturating_sub(duration.as_millis() as u64)
+    }
+
+    fn since(self, earlier: Self) -> Duration {
+        Duration::from_millis(self.saturating_sub(earlier))
+    }
+}
+
+// 当前时间，统一从这里获取以便替换时间戳类型
+pub fn now() -> DefaultTimestamp {
+    <DefaultTimestamp as Timestamp>::now()
+}
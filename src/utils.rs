@@ -1,77 +1,575 @@
+use crate::client::raydium_client;
+use crate::error::{MonitorError, Result};
+use async_trait::async_trait;
 use core::f64;
-use serde_json::Value;
-use solana_client::rpc_client::RpcClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 
-pub async fn get_sol_price() -> anyhow::Result<f64> {
-    let url =
-        "https://api-v3.raydium.io/pools/info/ids?ids=8sLbNZoA1cfnvMJLPfp98ZLAnFSYCFApfJKMbiXNLwxj";
-    let response = reqwest::get(url).await?.text().await?;
-    let json: Value = serde_json::from_str(&response)?;
+// 默认的 Solana 公共 RPC 节点（限流较严格）
+pub const DEFAULT_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
 
-    // 从JSON中提取价格
-    let price = json["data"][0]["price"]
-        .as_f64()
-        .ok_or(anyhow::anyhow!("Failed to extract price from JSON"))?;
+// SOL 价格缓存，避免每个池子都请求一次 SOL 价格
+#[derive(Debug, Clone)]
+pub struct SolPriceCache {
+    pub price: f64,
+    pub fetched_at: Option<Instant>,
+    pub ttl: Duration,
+    // 新价格偏离缓存价格超过该比例(%)时视为异常值，0 表示不检查
+    pub max_deviation: f64,
+    // 上一次被拒绝的价格；连续两次读数一致时认为是真实行情并接受
+    pub rejected_price: Option<f64>,
+}
+
+pub type SharedSolPriceCache = Arc<Mutex<SolPriceCache>>;
+
+impl SolPriceCache {
+    pub fn new(ttl: Duration) -> Self {
+        SolPriceCache {
+            price: 0.0,
+            fetched_at: None,
+            ttl,
+            max_deviation: 0.0,
+            rejected_price: None,
+        }
+    }
+
+    pub fn with_max_deviation(mut self, max_deviation: f64) -> Self {
+        self.max_deviation = max_deviation;
+        self
+    }
+
+    pub fn into_shared(self) -> SharedSolPriceCache {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn shared(ttl: Duration) -> SharedSolPriceCache {
+        Arc::new(Mutex::new(Self::new(ttl)))
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        self.fetched_at
+            .map(|fetched_at| fetched_at.elapsed() < self.ttl)
+            .unwrap_or(false)
+    }
+
+    // 记录新读到的价格并返回应使用的价格：偏离缓存价格过大时沿用缓存值，
+    // 除非与上一次被拒绝的价格一致（说明行情确实变化了）
+    pub fn update(&mut self, price: f64) -> f64 {
+        let deviates = |reference: f64| {
+            reference > 0.0 && ((price - reference) / reference * 100.0).abs() > self.max_deviation
+        };
+        self.fetched_at = Some(Instant::now());
+        if self.max_deviation > 0.0
+            && deviates(self.price)
+            && self.rejected_price.is_none_or(deviates)
+        {
+            tracing::warn!(
+                "Rejected SOL price {} deviating more than {}% from cached {}",
+                price,
+                self.max_deviation,
+                self.price
+            );
+            self.rejected_price = Some(price);
+            return self.price;
+        }
+
+        self.price = price;
+        self.rejected_price = None;
+        price
+    }
+}
+
+impl Default for SupplyCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SUPPLY_CACHE_TTL)
+    }
+}
+
+impl Default for SolPriceCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+// SOL 价格来源，get_sol_price 按顺序尝试，第一个成功的结果生效
+#[async_trait]
+pub trait SolPriceSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn sol_price(&self) -> Result<f64>;
+}
+
+// Raydium SOL/USDC 池子（首选来源，429 由客户端重试）
+pub struct RaydiumSolPrice;
+
+#[async_trait]
+impl SolPriceSource for RaydiumSolPrice {
+    fn name(&self) -> &str {
+        "raydium"
+    }
+
+    async fn sol_price(&self) -> Result<f64> {
+        raydium_client().sol_price().await
+    }
+}
+
+// CoinGecko simple price 接口，地址可通过 SOL_PRICE_FALLBACK_URL 覆盖
+#[cfg(feature = "coingecko-fallback")]
+pub const DEFAULT_COINGECKO_URL: &str =
+    "https://api.coingecko.com/api/v3/simple/price?ids=solana&vs_currencies=usd";
+
+#[cfg(feature = "coingecko-fallback")]
+pub struct CoinGeckoSolPrice {
+    pub url: String,
+}
+
+#[cfg(feature = "coingecko-fallback")]
+impl CoinGeckoSolPrice {
+    pub fn from_env() -> Self {
+        CoinGeckoSolPrice {
+            url: std::env::var("SOL_PRICE_FALLBACK_URL")
+                .unwrap_or_else(|_| DEFAULT_COINGECKO_URL.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "coingecko-fallback")]
+#[async_trait]
+impl SolPriceSource for CoinGeckoSolPrice {
+    fn name(&self) -> &str {
+        "coingecko"
+    }
+
+    async fn sol_price(&self) -> Result<f64> {
+        let json: serde_json::Value = reqwest::get(&self.url)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        json["solana"]["usd"]
+            .as_f64()
+            .ok_or(MonitorError::parse("CoinGecko SOL price"))
+    }
+}
+
+// 默认的来源顺序：Raydium，启用 coingecko-fallback 特性时再回退到 CoinGecko
+pub fn default_sol_price_sources() -> Vec<Box<dyn SolPriceSource>> {
+    #[allow(unused_mut)]
+    let mut sources: Vec<Box<dyn SolPriceSource>> = vec![Box::new(RaydiumSolPrice)];
+    #[cfg(feature = "coingecko-fallback")]
+    sources.push(Box::new(CoinGeckoSolPrice::from_env()));
+    sources
+}
+
+// 依次尝试各个来源，全部失败时返回包含每个来源错误的错误
+pub async fn get_sol_price_from(sources: &[Box<dyn SolPriceSource>]) -> Result<f64> {
+    let mut errors = Vec::new();
+    for source in sources {
+        match source.sol_price().await {
+            Ok(price) if price.is_finite() && price > 0.0 => return Ok(price),
+            Ok(price) => errors.push(format!("{}: invalid price {}", source.name(), price)),
+            Err(e) => {
+                tracing::warn!("Failed to get SOL price from {}: {}", source.name(), e);
+                errors.push(format!("{}: {}", source.name(), e));
+            }
+        }
+    }
+    Err(MonitorError::SolPrice(errors.join("; ")))
+}
+
+pub async fn get_sol_price() -> Result<f64> {
+    get_sol_price_from(&default_sol_price_sources()).await
+}
+
+// 从缓存读取 SOL 价格，过期时重新获取
+// 持有锁期间刷新，保证并发任务只发出一次请求
+pub async fn cached_sol_price(cache: &SharedSolPriceCache) -> Result<f64> {
+    cached_sol_price_from(cache, &default_sol_price_sources()).await
+}
+
+pub async fn cached_sol_price_from(
+    cache: &SharedSolPriceCache,
+    sources: &[Box<dyn SolPriceSource>],
+) -> Result<f64> {
+    let mut cache = cache.lock().await;
+    if cache.is_fresh() {
+        return Ok(cache.price);
+    }
 
+    let price = cache.update(get_sol_price_from(sources).await?);
+    tracing::debug!("Refreshed SOL price: {}", price);
     Ok(price)
 }
 
-pub async fn get_token_supply(token_address: &str) -> anyhow::Result<u64> {
-    let rpc_url = "https://api.mainnet-beta.solana.com";
-    let client = RpcClient::new(rpc_url.to_string());
+// RPC 限流时的额外重试次数，第 n 次重试前等待 500ms * 2^n
+const RPC_RATE_LIMIT_RETRIES: u32 = 2;
 
+// 使用非阻塞 RPC 客户端，等待响应时不会占用 tokio 工作线程
+pub async fn get_token_supply(client: &RpcClient, token_address: &str) -> Result<u64> {
     let token_pubkey = Pubkey::from_str(token_address)?;
-    let supply = client.get_token_supply(&token_pubkey)?;
-    log::debug!("SUPPLY: {:?}", supply);
-    Ok(supply.amount.parse().unwrap())
+    let mut attempt = 0;
+    let supply = loop {
+        let e = match client.get_token_supply(&token_pubkey).await {
+            Ok(supply) => break supply,
+            Err(e) => MonitorError::from(e),
+        };
+        if !e.is_rate_limited() || attempt >= RPC_RATE_LIMIT_RETRIES {
+            return Err(e);
+        }
+        let delay = Duration::from_millis(500 << attempt);
+        tracing::debug!(
+            retry_in_ms = delay.as_millis() as u64,
+            "Solana RPC rate limited, retrying supply lookup for {}",
+            token_address
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    };
+    tracing::debug!("SUPPLY: {:?}", supply);
+    supply
+        .amount
+        .parse()
+        .map_err(|_| MonitorError::parse("token supply"))
+}
+
+// 默认的代币供应量缓存时间，供应量很少变化
+pub const DEFAULT_SUPPLY_CACHE_TTL: Duration = Duration::from_secs(600);
+
+// 按代币地址缓存最近一次成功查询到的总供应量：ttl 内直接使用，不再请求 RPC；
+// 过期后重新查询，RPC 限流期间沿用旧值而不是丢掉市值
+#[derive(Debug)]
+pub struct SupplyCache {
+    supplies: HashMap<String, (u64, Instant)>,
+    pub ttl: Duration,
+    // 在该时间之前查询的供应量视为过期（显式刷新时设置）
+    invalidated_at: Option<Instant>,
+    // 最近一次刷新市值时 RPC 是否处于限流状态，只在状态变化时输出日志
+    pub rate_limited: bool,
+}
+
+pub type SharedSupplyCache = Arc<Mutex<SupplyCache>>;
+
+impl SupplyCache {
+    // ttl 为 0 表示每次都查询 RPC
+    pub fn new(ttl: Duration) -> Self {
+        SupplyCache {
+            supplies: HashMap::new(),
+            ttl,
+            invalidated_at: None,
+            rate_limited: false,
+        }
+    }
+
+    pub fn shared(ttl: Duration) -> SharedSupplyCache {
+        Arc::new(Mutex::new(Self::new(ttl)))
+    }
+
+    // 未过期的供应量
+    pub fn fresh(&self, token_address: &str) -> Option<u64> {
+        let (supply, fetched_at) = self.supplies.get(token_address)?;
+        let invalidated = self
+            .invalidated_at
+            .is_some_and(|invalidated_at| *fetched_at <= invalidated_at);
+        (fetched_at.elapsed() < self.ttl && !invalidated).then_some(*supply)
+    }
+
+    // 让所有缓存的供应量过期，下次查询时重新请求 RPC；旧值仍保留用于限流时回退
+    pub fn invalidate(&mut self) {
+        self.invalidated_at = Some(Instant::now());
+    }
+
+    pub fn insert(&mut self, token_address: &str, supply: u64) {
+        self.supplies
+            .insert(token_address.to_string(), (supply, Instant::now()));
+    }
+
+    // 最近一次成功查询的供应量及其距今时长
+    pub fn get(&self, token_address: &str) -> Option<(u64, Duration)> {
+        self.supplies
+            .get(token_address)
+            .map(|(supply, fetched_at)| (*supply, fetched_at.elapsed()))
+    }
+}
+
+// 优先使用未过期的缓存，否则查询供应量并记录到缓存；
+// RPC 限流且有旧值时返回旧值及其时长（未过期的缓存和新查询的结果时长为 None）
+pub async fn get_token_supply_or_cached(
+    client: &RpcClient,
+    supply_cache: &SharedSupplyCache,
+    token_address: &str,
+) -> Result<(u64, Option<Duration>)> {
+    if let Some(supply) = supply_cache.lock().await.fresh(token_address) {
+        return Ok((supply, None));
+    }
+    match get_token_supply(client, token_address).await {
+        Ok(supply) => {
+            supply_cache.lock().await.insert(token_address, supply);
+            Ok((supply, None))
+        }
+        Err(e) if e.is_rate_limited() => match supply_cache.lock().await.get(token_address) {
+            Some((supply, age)) => Ok((supply, Some(age))),
+            None => Err(e),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+pub const USDT_MINT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
+
+// 池子中另一边的报价资产；以 SOL 计价时还需要乘以 SOL 的美元价格
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteAsset {
+    Sol,
+    Usd,
+}
+
+impl QuoteAsset {
+    pub fn from_mint(address: &str) -> Option<Self> {
+        match address {
+            WSOL_MINT => Some(QuoteAsset::Sol),
+            USDC_MINT | USDT_MINT => Some(QuoteAsset::Usd),
+            _ => None,
+        }
+    }
 }
 
-pub async fn calculate_market_cap(token_data: &serde_json::Value) -> anyhow::Result<f64> {
-    let token_address = token_data["mintB"]["address"].as_str().unwrap();
-    let token_decimals = token_data["mintB"]["decimals"].as_u64().unwrap();
-    let price_in_sol = 1.0 / token_data["price"].as_f64().unwrap();
+// 需要计算市值的代币，以及它以报价资产计的价格
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenQuote {
+    pub token_address: String,
+    pub token_decimals: u64,
+    pub price: f64,
+    pub quote: QuoteAsset,
+}
+
+impl TokenQuote {
+    // Raydium 的 price 是 1 个 mintA 值多少个 mintB（标准池和 CLMM 池相同），
+    // 因此报价资产在 mintA 一侧时代币价格为 1/price，在 mintB 一侧时就是 price；
+    // 两侧都是或都不是报价资产时无法判断方向，返回 None
+    pub fn from_pool(mint_a: (&str, u64), mint_b: (&str, u64), price: f64) -> Option<Self> {
+        if price <= 0.0 || !price.is_finite() {
+            return None;
+        }
+        let ((token_address, token_decimals), quote, price) = match (
+            QuoteAsset::from_mint(mint_a.0),
+            QuoteAsset::from_mint(mint_b.0),
+        ) {
+            (Some(quote), None) => (mint_b, quote, 1.0 / price),
+            (None, Some(quote)) => (mint_a, quote, price),
+            _ => return None,
+        };
+        Some(TokenQuote {
+            token_address: token_address.to_string(),
+            token_decimals,
+            price,
+            quote,
+        })
+    }
+
+    // 从 API 返回的池子 JSON 中读取两侧代币和价格
+    pub fn from_json(token_data: &serde_json::Value) -> Result<Self> {
+        let mint = |side: &str| -> Result<(&str, u64)> {
+            Ok((
+                token_data[side]["address"]
+                    .as_str()
+                    .ok_or(MonitorError::parse(format!("{}.address", side)))?,
+                token_data[side]["decimals"]
+                    .as_u64()
+                    .ok_or(MonitorError::parse(format!("{}.decimals", side)))?,
+            ))
+        };
+        let price = token_data["price"]
+            .as_f64()
+            .ok_or(MonitorError::parse("price"))?;
+        Self::from_pool(mint("mintA")?, mint("mintB")?, price)
+            .ok_or(MonitorError::parse("quote asset"))
+    }
+
+    // 由原始总供应量（未除以精度）和 SOL 美元价格计算市值
+    pub fn market_cap(&self, total_supply: u64, sol_price: f64) -> f64 {
+        let price_in_usd = match self.quote {
+            QuoteAsset::Sol => self.price * sol_price,
+            QuoteAsset::Usd => self.price,
+        };
+        self.ui_supply(total_supply) * price_in_usd
+    }
 
-    // 获取 SOL 价格（以 USDC 计）
-    let sol_price = get_sol_price().await?;
+    // 按代币精度换算后的供应量，与池子储备的单位一致
+    pub fn ui_supply(&self, total_supply: u64) -> f64 {
+        total_supply as f64 / 10f64.powi(self.token_decimals as i32)
+    }
+}
 
-    // 将 SOL 价格转换为 USDC 价格
-    let price_in_usdc = price_in_sol * sol_price;
+pub async fn calculate_market_cap(
+    token_data: &serde_json::Value,
+    client: &RpcClient,
+    sol_price_cache: &SharedSolPriceCache,
+) -> Result<f64> {
+    calculate_market_cap_v1(&TokenQuote::from_json(token_data)?, client, sol_price_cache).await
+}
 
-    let total_supply = get_token_supply(token_address).await?;
-    let total_supply_adjusted = total_supply as f64 / 10f64.powi(token_decimals as i32);
+// 不需要 RPC 的粗略估值：由池子储备和 TVL 反推代币 B 的美元价格，
+// 再乘以池内代币 B 的数量。API 不返回总供应量，因此这是基于流动性的估值，通常低于真实市值
+pub fn fdv_estimate(token_data: &serde_json::Value) -> Option<f64> {
+    let amount_a = token_data["mintAmountA"].as_f64()?;
+    let amount_b = token_data["mintAmountB"].as_f64()?;
+    let price = token_data["price"].as_f64()?;
+    let tvl = token_data["tvl"].as_f64()?;
 
-    let market_cap = total_supply_adjusted * price_in_usdc;
+    // tvl = amount_a * price_a + amount_b * price_b，且 price_a = price * price_b
+    let denominator = amount_a * price + amount_b;
+    if denominator <= 0.0 {
+        return None;
+    }
+    let price_b_usd = tvl / denominator;
 
-    Ok(market_cap)
+    Some(price_b_usd * amount_b)
 }
 
 pub async fn calculate_market_cap_v1(
-    token_address: String,
-    token_decimals: u64,
-    price: f64,
-) -> anyhow::Result<f64> {
-    let token_address = token_address;
-    let token_decimals = token_decimals;
-    let price_in_sol = 1.0 / price;
+    quote: &TokenQuote,
+    client: &RpcClient,
+    sol_price_cache: &SharedSolPriceCache,
+) -> Result<f64> {
+    // 以美元稳定币计价时不需要 SOL 价格
+    let sol_price = match quote.quote {
+        QuoteAsset::Sol => cached_sol_price(sol_price_cache).await?,
+        QuoteAsset::Usd => 0.0,
+    };
+    let total_supply = get_token_supply(client, &quote.token_address).await?;
 
-    // 获取 SOL 价格（以 USDC 计）
-    let sol_price = get_sol_price().await?;
+    Ok(quote.market_cap(total_supply, sol_price))
+}
 
-    // 将 SOL 价格转换为 USDC 价格
-    let price_in_usdc = price_in_sol * sol_price;
+// 同 calculate_market_cap_v1，RPC 限流时使用缓存的供应量，同时返回所用供应量的时长
+pub async fn calculate_market_cap_with_supply_cache(
+    quote: &TokenQuote,
+    client: &RpcClient,
+    sol_price_cache: &SharedSolPriceCache,
+    supply_cache: &SharedSupplyCache,
+) -> Result<(f64, u64, Option<Duration>)> {
+    let sol_price = match quote.quote {
+        QuoteAsset::Sol => cached_sol_price(sol_price_cache).await?,
+        QuoteAsset::Usd => 0.0,
+    };
+    let (total_supply, supply_age) =
+        get_token_supply_or_cached(client, supply_cache, &quote.token_address).await?;
 
-    let total_supply = get_token_supply(&token_address).await?;
-    let total_supply_adjusted = total_supply as f64 / 10f64.powi(token_decimals as i32);
+    Ok((
+        quote.market_cap(total_supply, sol_price),
+        total_supply,
+        supply_age,
+    ))
+}
 
-    let market_cap = total_supply_adjusted * price_in_usdc;
+#[test]
+fn test_token_supply_does_not_block_runtime() {
+    // 单线程运行时：如果 RPC 调用是阻塞的，超时计时器将无法触发
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    // 只监听不响应的本地端口，请求会一直挂起
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = RpcClient::new(format!("http://{}", listener.local_addr().unwrap()));
+
+    rt.block_on(async {
+        let start = Instant::now();
+        let result = tokio::time::timeout(
+            Duration::from_millis(200),
+            get_token_supply(&client, "So11111111111111111111111111111111111111112"),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    });
+}
+
+#[test]
+fn test_sol_price_falls_back_to_next_source() {
+    // 固定返回结果的模拟来源
+    struct MockSource(&'static str, Option<f64>);
+
+    #[async_trait]
+    impl SolPriceSource for MockSource {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn sol_price(&self) -> Result<f64> {
+            self.1.ok_or(MonitorError::parse("unavailable"))
+        }
+    }
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let sources: Vec<Box<dyn SolPriceSource>> = vec![
+            Box::new(MockSource("primary", None)),
+            Box::new(MockSource("zero", Some(0.0))),
+            Box::new(MockSource("fallback", Some(150.0))),
+        ];
+        assert_eq!(get_sol_price_from(&sources).await.unwrap(), 150.0);
+
+        let sources: Vec<Box<dyn SolPriceSource>> = vec![
+            Box::new(MockSource("primary", None)),
+            Box::new(MockSource("fallback", None)),
+        ];
+        let err = get_sol_price_from(&sources).await.unwrap_err().to_string();
+        assert!(err.contains("primary: Failed to parse unavailable"));
+        assert!(err.contains("fallback: Failed to parse unavailable"));
+    });
+}
 
-    Ok(market_cap)
+#[test]
+fn test_spiked_sol_price_falls_back_to_cached_value() {
+    struct FixedSource(f64);
+
+    #[async_trait]
+    impl SolPriceSource for FixedSource {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn sol_price(&self) -> Result<f64> {
+            Ok(self.0)
+        }
+    }
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        // ttl 为 0，每次调用都会重新读取
+        let cache = SolPriceCache::new(Duration::ZERO)
+            .with_max_deviation(20.0)
+            .into_shared();
+        let read = |price: f64| {
+            let cache = cache.clone();
+            async move {
+                let sources: Vec<Box<dyn SolPriceSource>> = vec![Box::new(FixedSource(price))];
+                cached_sol_price_from(&cache, &sources).await.unwrap()
+            }
+        };
+
+        assert_eq!(read(150.0).await, 150.0);
+        assert_eq!(read(160.0).await, 160.0);
+        // 瞬时尖峰被拒绝，沿用缓存值
+        assert_eq!(read(1600.0).await, 160.0);
+        assert_eq!(read(162.0).await, 162.0);
+        // 连续两次一致的大幅变化视为真实行情
+        assert_eq!(read(100.0).await, 162.0);
+        assert_eq!(read(101.0).await, 101.0);
+    });
 }
 
 #[test]
-fn test_market_cap() -> Result<(), Box<dyn std::error::Error>> {
+fn test_market_cap() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let rt = tokio::runtime::Runtime::new().unwrap();
     // 假设token_data是您之前提供的API返回的JSON数据
     let str = r#"{
@@ -164,10 +662,214 @@ fn test_market_cap() -> Result<(), Box<dyn std::error::Error>> {
         let token_data: serde_json::Value = serde_json::from_str(str).unwrap();
         println!("{:?}", token_data);
 
-        let market_cap = calculate_market_cap(&token_data).await.unwrap();
+        let sol_price_cache = SolPriceCache::shared(Duration::from_secs(60));
+        let client = RpcClient::new(DEFAULT_RPC_URL.to_string());
+        let market_cap = calculate_market_cap(&token_data, &client, &sol_price_cache)
+            .await
+            .unwrap();
 
         println!("Estimated market cap: ${:.2}", market_cap);
     });
 
     Ok(())
 }
+
+#[test]
+fn test_clmm_quote_orientation() {
+    // CLMM 池子 RAY/WSOL：mintA 是 RAY，price 是 1 RAY 值多少 SOL
+    let clmm: serde_json::Value = serde_json::from_str(
+        r#"{
+            "type": "Concentrated",
+            "programId": "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK",
+            "id": "2AXXcN6oN9bBT5owwmTH53C7QHUXvhLeu718Kqt8rvY2",
+            "mintA": {
+              "chainId": 101,
+              "address": "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R",
+              "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+              "symbol": "RAY",
+              "name": "Raydium",
+              "decimals": 6
+            },
+            "mintB": {
+              "chainId": 101,
+              "address": "So11111111111111111111111111111111111111112",
+              "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+              "symbol": "WSOL",
+              "name": "Wrapped SOL",
+              "decimals": 9
+            },
+            "config": {
+              "id": "9iFER3bpjf1PTTCQCfTRu17EJgvsxo9pVyA9QWwEuX4x",
+              "index": 4,
+              "protocolFeeRate": 120000,
+              "tradeFeeRate": 2500,
+              "tickSpacing": 60
+            },
+            "price": 0.012537,
+            "mintAmountA": 1208453.151622,
+            "mintAmountB": 15104.271953017,
+            "feeRate": 0.0025,
+            "openTime": "1723037622",
+            "tvl": 4832611.38,
+            "day": { "volume": 2391842.55, "apr": 57.3, "feeApr": 45.1, "priceMin": 0.0121, "priceMax": 0.0129 },
+            "pooltype": []
+        }"#,
+    )
+    .unwrap();
+
+    let quote = TokenQuote::from_json(&clmm).unwrap();
+    assert_eq!(
+        quote.token_address,
+        "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R"
+    );
+    assert_eq!(quote.token_decimals, 6);
+    assert_eq!(quote.quote, QuoteAsset::Sol);
+    assert_eq!(quote.price, 0.012537);
+
+    // 555,000,000 RAY，SOL 为 160 美元：0.012537 * 160 * 555M
+    let supply = 555_000_000 * 10u64.pow(6);
+    let market_cap = quote.market_cap(supply, 160.0);
+    assert!((market_cap - 1_113_285_600.0).abs() < 1.0);
+
+    // 直接取 mintB 并求倒数会把 WSOL 当作代币，价格相差约 6000 倍
+    let naive = 1.0 / 0.012537 * 160.0 * 555_000_000.0;
+    assert!(naive / market_cap > 6000.0);
+}
+
+#[test]
+fn test_quote_orientation_for_sol_and_stable_pairs() {
+    // 标准池 WSOL/$slop：报价资产在 mintA 一侧，代币价格是 1/price
+    let quote = TokenQuote::from_pool(
+        (WSOL_MINT, 9),
+        ("FqvtZ2UFR9we82Ni4LeacC1zyTiQ77usDo31DUokpump", 6),
+        6948.933948075416,
+    )
+    .unwrap();
+    assert_eq!(
+        quote.token_address,
+        "FqvtZ2UFR9we82Ni4LeacC1zyTiQ77usDo31DUokpump"
+    );
+    assert_eq!(quote.price, 1.0 / 6948.933948075416);
+    assert_eq!(quote.quote, QuoteAsset::Sol);
+
+    // 以 USDC 计价时不乘 SOL 价格
+    let quote = TokenQuote::from_pool(
+        ("JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", 6),
+        (USDC_MINT, 6),
+        0.85,
+    )
+    .unwrap();
+    assert_eq!(quote.quote, QuoteAsset::Usd);
+    assert_eq!(
+        quote.market_cap(10_000_000_000 * 10u64.pow(6), 160.0),
+        8_500_000_000.0
+    );
+
+    // 两侧都是报价资产或都不是时无法判断方向
+    assert!(TokenQuote::from_pool((WSOL_MINT, 9), (USDC_MINT, 6), 160.0).is_none());
+    assert!(TokenQuote::from_pool(
+        ("JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", 6),
+        ("4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R", 6),
+        1.2,
+    )
+    .is_none());
+}
+
+#[test]
+fn test_rate_limited_supply_falls_back_to_cache() {
+    use solana_client::client_error::ClientError;
+    use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+
+    let rate_limited = MonitorError::from(ClientError::from(RpcError::RpcResponseError {
+        code: 429,
+        message: "Too many requests for a specific RPC call".to_string(),
+        data: RpcResponseErrorData::Empty,
+    }));
+    assert!(rate_limited.is_rate_limited());
+    assert!(!MonitorError::parse("token supply").is_rate_limited());
+
+    let mut cache = SupplyCache::default();
+    assert!(cache.get(WSOL_MINT).is_none());
+    cache.insert(WSOL_MINT, 1_000_000);
+    let (supply, age) = cache.get(WSOL_MINT).unwrap();
+    assert_eq!(supply, 1_000_000);
+    assert!(age < Duration::from_secs(1));
+
+    // 模拟的 RPC 节点：每个请求都返回 429 限流错误
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = RpcClient::new(format!("http://{}", listener.local_addr().unwrap()));
+    std::thread::spawn(move || {
+        use std::io::{Read, Write};
+        for mut stream in listener.incoming().flatten() {
+            // 读完请求头和请求体后再响应
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while let Ok(n @ 1..) = stream.read(&mut buf) {
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            let line = line.to_ascii_lowercase();
+                            line.strip_prefix("content-length:")
+                                .and_then(|value| value.trim().parse::<usize>().ok())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+            let body =
+                r#"{"jsonrpc":"2.0","error":{"code":429,"message":"Too many requests"},"id":1}"#;
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        // 缓存已过期但有旧值：限流时沿用旧值并返回其时长
+        let supply_cache = SupplyCache::shared(DEFAULT_SUPPLY_CACHE_TTL);
+        supply_cache.lock().await.insert(WSOL_MINT, 1_000_000);
+        supply_cache.lock().await.invalidate();
+        let (supply, age) = get_token_supply_or_cached(&client, &supply_cache, WSOL_MINT)
+            .await
+            .unwrap();
+        assert_eq!(supply, 1_000_000);
+        assert!(age.is_some());
+
+        // 没有旧值时返回限流错误
+        let e = get_token_supply_or_cached(&client, &supply_cache, USDC_MINT)
+            .await
+            .unwrap_err();
+        assert!(e.is_rate_limited());
+    });
+}
+
+#[test]
+fn test_supply_cache_ttl_and_invalidation() {
+    let mut cache = SupplyCache::new(Duration::from_secs(600));
+    cache.insert(WSOL_MINT, 1_000_000);
+    assert_eq!(cache.fresh(WSOL_MINT), Some(1_000_000));
+    assert_eq!(cache.fresh(USDC_MINT), None);
+
+    // 显式刷新后不再视为新鲜，但旧值仍可用于限流回退
+    cache.invalidate();
+    assert_eq!(cache.fresh(WSOL_MINT), None);
+    assert_eq!(
+        cache.get(WSOL_MINT).map(|(supply, _)| supply),
+        Some(1_000_000)
+    );
+    cache.insert(WSOL_MINT, 2_000_000);
+    assert_eq!(cache.fresh(WSOL_MINT), Some(2_000_000));
+
+    // ttl 为 0 时不缓存
+    let mut cache = SupplyCache::new(Duration::ZERO);
+    cache.insert(WSOL_MINT, 1_000_000);
+    assert_eq!(cache.fresh(WSOL_MINT), None);
+}
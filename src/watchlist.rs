@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+// 关注的池子ID列表文件
+// .json 文件保存为 JSON 数组，其他文件每行一个ID，# 开头的行是注释。
+// 读取和保存都只按扩展名判断格式，更新时不会改变文件原来的格式
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+pub fn load_watchlist(path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read watchlist {}", path.display()))?;
+    if is_json(path) {
+        return serde_json::from_str(&content)
+            .with_context(|| format!("Invalid JSON watchlist {}", path.display()));
+    }
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+pub fn save_watchlist(path: &Path, ids: &[String]) -> Result<()> {
+    let content = if is_json(path) {
+        serde_json::to_string_pretty(ids)? + "\n"
+    } else {
+        ids.iter().map(|id| format!("{}\n", id)).collect()
+    };
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write watchlist {}", path.display()))
+}
+
+// 添加和移除ID后保存，文件不存在时视为空列表；返回更新后的列表
+pub fn update_watchlist(path: &Path, add: &[String], remove: &[String]) -> Result<Vec<String>> {
+    let mut ids = if path.exists() {
+        load_watchlist(path)?
+    } else {
+        Vec::new()
+    };
+    for id in add {
+        if !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+    ids.retain(|id| !remove.contains(id));
+    save_watchlist(path, &ids)?;
+    Ok(ids)
+}
+
+#[test]
+fn test_watchlist_round_trips_json_and_plain_text() {
+    let dir = std::env::temp_dir().join(format!("watchlist-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // 纯文本：注释和空行被忽略，以 [ 开头的ID不会被当成 JSON
+    let text = dir.join("pools.txt");
+    std::fs::write(
+        &text,
+        "# watched pools\n\n  poolA  \n[poolB\n# old\n\npoolC\n",
+    )
+    .unwrap();
+    assert_eq!(load_watchlist(&text).unwrap(), ["poolA", "[poolB", "poolC"]);
+    let ids = update_watchlist(&text, &["poolD".to_string()], &["poolA".to_string()]).unwrap();
+    assert_eq!(ids, ["[poolB", "poolC", "poolD"]);
+    assert_eq!(
+        std::fs::read_to_string(&text).unwrap(),
+        "[poolB\npoolC\npoolD\n"
+    );
+    assert_eq!(load_watchlist(&text).unwrap(), ids);
+
+    // JSON：保存后仍是 JSON 数组
+    let json = dir.join("pools.json");
+    std::fs::write(&json, "[\"poolA\", \"poolB\"]").unwrap();
+    let ids = update_watchlist(&json, &["poolC".to_string()], &[]).unwrap();
+    assert_eq!(ids, ["poolA", "poolB", "poolC"]);
+    let saved: Vec<String> =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(saved, ids);
+    assert_eq!(load_watchlist(&json).unwrap(), ids);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}